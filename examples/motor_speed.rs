@@ -0,0 +1,44 @@
+//! Speed control of a simulated DC motor following a setpoint schedule, with a load
+//! disturbance halfway through.
+//!
+//! Run with `cargo run --example motor_speed`.
+
+// motor: tm * dw/dt = km * volts - w - load
+const MOTOR_GAIN: f64 = 100.0; // rpm per volt
+const MECHANICAL_TIME_CONSTANT: f64 = 0.2; // s
+const SUPPLY: f64 = 24.0; // V
+const TDELTA: f64 = 0.001; // s
+
+fn main() {
+    // (setpoint in rpm, duration in s)
+    let schedule = pid_ctrl::SetpointSchedule::new([(1000.0, 1.0), (1500.0, 1.0), (500.0, 1.0)]);
+
+    let mut pid = pid_ctrl::PidCtrl::new_with_pid(0.02, 0.1, 0.0005);
+    // the H-bridge drives -SUPPLY..SUPPLY
+    pid.limits.set_limit(SUPPLY);
+    pid.set_tracking_gain(5.0).unwrap();
+    pid.kd.set_ewma_alpha(0.2).unwrap();
+
+    let mut speed = 0.0;
+    pid.init(0.0, speed);
+
+    println!(
+        "{:>6} {:>8} {:>11} {:>8} {:>8} {:>8} {:>8}",
+        "time", "setpoint", "measurement", "p", "i", "d", "output"
+    );
+    let steps = (schedule.total_duration() / TDELTA) as usize;
+    for k in 0..=steps {
+        let time = k as f64 * TDELTA;
+        let out = pid.step_schedule(&schedule, time, speed, TDELTA);
+        if k % 100 == 0 {
+            println!(
+                "{:>6.2} {:>8.0} {:>11.1} {:>8.3} {:>8.3} {:>8.3} {:>8.3}",
+                time, pid.setpoint, speed, out.p, out.i, out.d, out.out
+            );
+        }
+
+        // a load torque, expressed in rpm of lost speed, appears at 1.5 s
+        let load = if time >= 1.5 { 300.0 } else { 0.0 };
+        speed += TDELTA * (MOTOR_GAIN * out.out - speed - load) / MECHANICAL_TIME_CONSTANT;
+    }
+}
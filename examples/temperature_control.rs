@@ -0,0 +1,48 @@
+//! Heater control of a simulated first-order plant with dead time.
+//!
+//! Run with `cargo run --example temperature_control`.
+use std::collections::VecDeque;
+
+// plant: tau * dT/dt = gain * power - (T - AMBIENT), with the heater power arriving DEAD_TIME late
+const AMBIENT: f64 = 20.0;
+const PROCESS_GAIN: f64 = 0.5; // degC per % of heater power
+const TIME_CONSTANT: f64 = 10.0; // s
+const DEAD_TIME: f64 = 2.0; // s
+const TDELTA: f64 = 0.1; // s
+
+fn main() {
+    let setpoint = 60.0;
+
+    // Ziegler-Nichols reaction curve tuning: kc = 1.2 * tau / (K * L), ti = 2 * L, td = L / 2
+    let kc = 1.2 * TIME_CONSTANT / (PROCESS_GAIN * DEAD_TIME);
+    let mut pid = pid_ctrl::PidCtrl::new_with_standard_form(kc, 2.0 * DEAD_TIME, 0.5 * DEAD_TIME);
+    // the heater can only deliver 0..100 % power
+    pid.limits = pid_ctrl::Limits::try_new(0.0, 100.0).unwrap();
+    // back-calculation anti-windup with tracking time sqrt(ti * td)
+    pid.set_tracking_gain(1.0 / (2.0 * DEAD_TIME * 0.5 * DEAD_TIME).sqrt()).unwrap();
+    // smooth the derivative a little
+    pid.kd.set_ewma_alpha(0.5).unwrap();
+
+    let mut temperature = AMBIENT;
+    pid.init(setpoint, temperature);
+    let mut delay: VecDeque<f64> = std::iter::repeat_n(0.0, (DEAD_TIME / TDELTA) as usize).collect();
+
+    println!(
+        "{:>6} {:>8} {:>11} {:>8} {:>8} {:>8} {:>8}",
+        "time", "setpoint", "measurement", "p", "i", "d", "output"
+    );
+    for k in 0..=1200 {
+        let time = k as f64 * TDELTA;
+        let out = pid.step(pid_ctrl::PidIn::new(temperature, TDELTA));
+        if k % 20 == 0 {
+            println!(
+                "{:>6.1} {:>8.1} {:>11.2} {:>8.2} {:>8.2} {:>8.2} {:>8.2}",
+                time, setpoint, temperature, out.p, out.i, out.d, out.out
+            );
+        }
+
+        delay.push_back(out.out);
+        let power = delay.pop_front().unwrap();
+        temperature += TDELTA * (PROCESS_GAIN * power - (temperature - AMBIENT)) / TIME_CONSTANT;
+    }
+}
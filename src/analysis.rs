@@ -0,0 +1,116 @@
+//! Offline analysis helpers that work on gains alone, without any controller state.
+use crate::math::{self, cast};
+use num_traits::float::FloatCore;
+
+/// Magnitude and phase (in degrees) of the discrete PID transfer function at angular
+/// frequency `omega`, for a controller stepped every `sample_period`.
+///
+/// The transfer function matches what `PidCtrl::step` computes: rectangular integration
+/// `ki * T / (1 - z^-1)` and a backward-difference derivative `kd * (1 - z^-1) / T`.
+pub fn pid_frequency_response<T: FloatCore + Default>(kp: T, ki: T, kd: T, omega: T, sample_period: T) -> (T, T) {
+    let (sin, cos) = math::sin_cos(omega * sample_period);
+    // 1 - z^-1 evaluated at z = exp(j * omega * T)
+    let a = T::one() - cos;
+    let b = sin;
+    let den = a * a + b * b;
+    let (re, im) = if den == T::zero() {
+        // DC: the integrator dominates unless it is disabled
+        if ki == T::zero() {
+            (kp, T::zero())
+        } else {
+            (T::zero(), -ki.signum() * T::infinity())
+        }
+    } else {
+        (
+            kp + ki * sample_period * a / den + kd * a / sample_period,
+            -ki * sample_period * b / den + kd * b / sample_period,
+        )
+    };
+    let magnitude = if re.is_infinite() || im.is_infinite() {
+        T::infinity()
+    } else {
+        math::sqrt(re * re + im * im)
+    };
+    (magnitude, math::atan2(im, re).to_degrees())
+}
+
+/// `contribution / total`, or zero when `total` is zero.
+pub fn contribution_fraction<T: FloatCore + Default>(contribution: T, total: T) -> T {
+    if total == T::zero() {
+        T::zero()
+    } else {
+        contribution / total
+    }
+}
+
+fn cohen_coon_base<T: FloatCore>(process_gain: T, dead_time: T, time_constant: T) -> (T, T) {
+    let ratio = dead_time / time_constant;
+    (time_constant / (process_gain * dead_time), ratio)
+}
+
+/// Cohen-Coon P tuning from a first-order-plus-dead-time step response with gain
+/// `process_gain`, `dead_time` and `time_constant`. Returns `(kp, ki, kd)` for
+/// `PidCtrl::new_with_pid`, with `ki` and `kd` zero.
+pub fn cohen_coon_p<T: FloatCore + Default>(process_gain: T, dead_time: T, time_constant: T) -> (T, T, T) {
+    let (base, r) = cohen_coon_base(process_gain, dead_time, time_constant);
+    (base * (T::one() + r / cast(3.0)), T::zero(), T::zero())
+}
+
+/// Cohen-Coon PI tuning, see `cohen_coon_p`. `ki` is `kc / ti`.
+pub fn cohen_coon_pi<T: FloatCore + Default>(process_gain: T, dead_time: T, time_constant: T) -> (T, T, T) {
+    let (base, r) = cohen_coon_base(process_gain, dead_time, time_constant);
+    let kc = base * (cast::<T>(0.9) + r / cast(12.0));
+    let ti = dead_time * (cast::<T>(30.0) + cast::<T>(3.0) * r) / (cast::<T>(9.0) + cast::<T>(20.0) * r);
+    (kc, kc / ti, T::zero())
+}
+
+/// Cohen-Coon PID tuning, see `cohen_coon_p`. `ki` is `kc / ti` and `kd` is `kc * td`.
+pub fn cohen_coon_pid<T: FloatCore + Default>(process_gain: T, dead_time: T, time_constant: T) -> (T, T, T) {
+    let (base, r) = cohen_coon_base(process_gain, dead_time, time_constant);
+    let kc = base * (cast::<T>(4.0) / cast(3.0) + r / cast(4.0));
+    let ti = dead_time * (cast::<T>(32.0) + cast::<T>(6.0) * r) / (cast::<T>(13.0) + cast::<T>(8.0) * r);
+    let td = cast::<T>(4.0) * dead_time / (cast::<T>(11.0) + cast::<T>(2.0) * r);
+    (kc, kc / ti, kc * td)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn contribution_fraction() {
+        assert_eq!(super::contribution_fraction(1.0, 4.0), 0.25);
+        assert_eq!(super::contribution_fraction(1.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn cohen_coon() {
+        // Tabulated Cohen-Coon settings for a = K * L / T = 0.4 and tau = L / (L + T) = 1/6,
+        // from the normalized form in Astrom and Hagglund, "PID Controllers" (1995), whose
+        // rounded coefficients agree with the original formulas to a couple of percent.
+        let close = |a: f64, b: f64| ((a - b) / b).abs() < 0.025;
+        let (kp, ki, kd) = super::cohen_coon_p(2.0f64, 2.0, 10.0);
+        assert!(close(kp, 2.675) && ki == 0.0 && kd == 0.0);
+        let (kp, ki, kd) = super::cohen_coon_pi(2.0f64, 2.0, 10.0);
+        assert!(close(kp, 2.291) && close(kp / ki, 4.667) && kd == 0.0);
+        let (kp, ki, kd) = super::cohen_coon_pid(2.0f64, 2.0, 10.0);
+        assert!(close(kp, 3.497) && close(kp / ki, 4.635) && close(kd / kp, 0.7129));
+    }
+
+    #[test]
+    fn pid_frequency_response() {
+        let (mag, phase) = super::pid_frequency_response(2.0f64, 0.0, 0.0, 1.0, 0.01);
+        assert!((mag - 2.0).abs() < 1e-12 && phase.abs() < 1e-12);
+
+        // at low frequency the integrator lags by ~90 degrees with gain ki / omega
+        let (mag, phase) = super::pid_frequency_response(0.0f64, 3.0, 0.0, 1e-3, 1e-3);
+        assert!((mag - 3.0 / 1e-3).abs() / (3.0 / 1e-3) < 1e-3);
+        assert!((phase + 90.0).abs() < 0.01);
+
+        // and the derivative leads by ~90 degrees with gain kd * omega
+        let (mag, phase) = super::pid_frequency_response(0.0f64, 0.0, 0.5, 2.0, 1e-4);
+        assert!((mag - 1.0).abs() < 1e-3);
+        assert!((phase - 90.0).abs() < 0.01);
+
+        let (mag, phase) = super::pid_frequency_response(1.0f64, 1.0, 0.0, 0.0, 0.1);
+        assert!(mag.is_infinite() && phase == -90.0);
+    }
+}
@@ -0,0 +1,269 @@
+//! A fixed-point controller for targets without an FPU, behind the `fixed-point` feature.
+//!
+//! `FixedI16F16` wraps `fixed::types::I16F16` and implements `FloatCore` so it can be used
+//! as any `T` of the crate. Expect less precision than `f32`: values are multiples of
+//! `2^-16` (about `1.5e-5`), so small gains or time deltas lose most of their digits, and
+//! an integrator fed a tiny `ki * error * tdelta` every step can stop moving altogether.
+//! The range is `±32768`; arithmetic saturates instead of overflowing, and the two extremes
+//! stand in for the infinities, so unlimited `Limits` keep working. There is no NaN:
+//! `nan()` is zero and `is_nan()` is always false, so results that would be NaN in floating
+//! point, such as `0 / 0`, are zero instead.
+use crate::{PidCtrl, PidError};
+use core::num::FpCategory;
+use core::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use fixed::types::I16F16;
+use num_traits::float::FloatCore;
+use num_traits::{Num, NumCast, One, ToPrimitive, Zero};
+
+/// A `PidCtrl` computing in `I16F16`.
+pub type FixedPidCtrl = PidCtrl<FixedI16F16>;
+
+/// An `I16F16` usable as the `T` of `PidCtrl`; see the module documentation for how it
+/// differs from a float.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct FixedI16F16(pub I16F16);
+
+impl FixedI16F16 {
+    /// Converts `val`, saturating values outside the range; NaN becomes zero.
+    pub fn from_f32(val: f32) -> Self {
+        if val.is_nan() {
+            return Self::zero();
+        }
+        FixedI16F16(I16F16::saturating_from_num(val))
+    }
+
+    pub fn to_f32(self) -> f32 {
+        if self.is_infinite() {
+            return if self.0 > I16F16::ZERO { f32::INFINITY } else { f32::NEG_INFINITY };
+        }
+        self.0.to_num()
+    }
+}
+
+impl Add for FixedI16F16 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        FixedI16F16(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Sub for FixedI16F16 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        FixedI16F16(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Mul for FixedI16F16 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        FixedI16F16(self.0.saturating_mul(rhs.0))
+    }
+}
+
+impl Div for FixedI16F16 {
+    type Output = Self;
+
+    // division by zero saturates like the infinities it stands for
+    fn div(self, rhs: Self) -> Self {
+        if rhs.0 == I16F16::ZERO {
+            return match self.0.cmp(&I16F16::ZERO) {
+                core::cmp::Ordering::Greater => Self::infinity(),
+                core::cmp::Ordering::Less => Self::neg_infinity(),
+                core::cmp::Ordering::Equal => Self::zero(),
+            };
+        }
+        FixedI16F16(self.0.saturating_div(rhs.0))
+    }
+}
+
+impl Rem for FixedI16F16 {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        FixedI16F16(self.0.checked_rem(rhs.0).unwrap_or(I16F16::ZERO))
+    }
+}
+
+impl Neg for FixedI16F16 {
+    type Output = Self;
+
+    // swaps the infinities, although `I16F16::MIN` has no positive twin
+    fn neg(self) -> Self {
+        if self.0 == I16F16::MIN {
+            Self::infinity()
+        } else if self.0 == I16F16::MAX {
+            Self::neg_infinity()
+        } else {
+            FixedI16F16(-self.0)
+        }
+    }
+}
+
+impl Zero for FixedI16F16 {
+    fn zero() -> Self {
+        FixedI16F16(I16F16::ZERO)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == I16F16::ZERO
+    }
+}
+
+impl One for FixedI16F16 {
+    fn one() -> Self {
+        FixedI16F16(I16F16::ONE)
+    }
+}
+
+impl Num for FixedI16F16 {
+    type FromStrRadixErr = PidError;
+
+    /// Accepts radix 2, 8, 10 and 16; anything else, or an unparsable or out of range
+    /// string, is `PidError::InvalidValue`.
+    fn from_str_radix(src: &str, radix: u32) -> Result<Self, PidError> {
+        let parsed = match radix {
+            2 => I16F16::from_str_binary(src),
+            8 => I16F16::from_str_octal(src),
+            10 => src.parse(),
+            16 => I16F16::from_str_hex(src),
+            _ => return Err(PidError::InvalidValue),
+        };
+        parsed.map(FixedI16F16).map_err(|_| PidError::InvalidValue)
+    }
+}
+
+impl ToPrimitive for FixedI16F16 {
+    fn to_i64(&self) -> Option<i64> {
+        if self.is_infinite() {
+            return None;
+        }
+        Some(self.0.round_to_zero().to_num())
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        if self.is_infinite() || self.0 <= -I16F16::ONE {
+            return None;
+        }
+        Some(self.0.round_to_zero().to_num())
+    }
+
+    fn to_f32(&self) -> Option<f32> {
+        Some(FixedI16F16::to_f32(*self))
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        if self.is_infinite() {
+            return Some(if self.0 > I16F16::ZERO { f64::INFINITY } else { f64::NEG_INFINITY });
+        }
+        Some(self.0.to_num())
+    }
+}
+
+impl NumCast for FixedI16F16 {
+    /// Saturates values outside the range, including the infinities, and rejects NaN.
+    fn from<N: ToPrimitive>(n: N) -> Option<Self> {
+        let val = n.to_f64()?;
+        if val.is_nan() {
+            return None;
+        }
+        Some(FixedI16F16(I16F16::saturating_from_num(val)))
+    }
+}
+
+impl FloatCore for FixedI16F16 {
+    fn infinity() -> Self {
+        FixedI16F16(I16F16::MAX)
+    }
+
+    fn neg_infinity() -> Self {
+        FixedI16F16(I16F16::MIN)
+    }
+
+    fn nan() -> Self {
+        Self::zero()
+    }
+
+    fn neg_zero() -> Self {
+        Self::zero()
+    }
+
+    fn min_value() -> Self {
+        FixedI16F16(I16F16::MIN + I16F16::DELTA)
+    }
+
+    fn min_positive_value() -> Self {
+        FixedI16F16(I16F16::DELTA)
+    }
+
+    fn epsilon() -> Self {
+        FixedI16F16(I16F16::DELTA)
+    }
+
+    fn max_value() -> Self {
+        FixedI16F16(I16F16::MAX - I16F16::DELTA)
+    }
+
+    fn classify(self) -> FpCategory {
+        if self.is_zero() {
+            FpCategory::Zero
+        } else if self.0 == I16F16::MAX || self.0 == I16F16::MIN {
+            FpCategory::Infinite
+        } else {
+            FpCategory::Normal
+        }
+    }
+
+    fn to_degrees(self) -> Self {
+        self * FixedI16F16(I16F16::saturating_from_num(180.0 / core::f64::consts::PI))
+    }
+
+    fn to_radians(self) -> Self {
+        self * FixedI16F16(I16F16::saturating_from_num(core::f64::consts::PI / 180.0))
+    }
+
+    // mantissa * 2^exponent with the raw bits as the mantissa; zero is positive
+    fn integer_decode(self) -> (u64, i16, i8) {
+        let bits = self.0.to_bits();
+        let sign = if bits < 0 { -1 } else { 1 };
+        (bits.unsigned_abs() as u64, -16, sign)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FixedI16F16;
+    use num_traits::float::FloatCore;
+
+    #[test]
+    fn saturates_to_infinity() {
+        let big = FixedI16F16::from_f32(30000.0);
+        assert!((big + big).is_infinite());
+        assert_eq!(-(big * big), FixedI16F16::neg_infinity());
+        assert_eq!(big / FixedI16F16::from_f32(0.0), FixedI16F16::infinity());
+        assert!(FixedI16F16::from_f32(f32::NAN).is_finite());
+        assert_eq!(FixedI16F16::infinity().to_f32(), f32::INFINITY);
+        assert_eq!(FixedI16F16::from_f32(-2.75).abs(), FixedI16F16::from_f32(2.75));
+    }
+
+    #[test]
+    fn matches_f32_controller() {
+        let fix = FixedI16F16::from_f32;
+        let mut fixed = super::FixedPidCtrl::new_with_pid(fix(2.0), fix(0.5), fix(0.25));
+        fixed.limits.set_limit(fix(10.0));
+        fixed.setpoint = fix(1.0);
+        let mut float = crate::PidCtrl::new_with_pid(2.0f32, 0.5, 0.25);
+        float.limits.set_limit(10.0);
+        float.setpoint = 1.0;
+        let mut measurement = 0.0;
+        for _ in 0..50 {
+            let out = float.step(crate::PidIn::new(measurement, 0.1)).out;
+            let fixed_out = fixed.step(crate::PidIn::new(fix(measurement), fix(0.1))).out;
+            assert!((fixed_out.to_f32() - out).abs() < 1e-2);
+            measurement += 0.1 * (out - measurement);
+        }
+    }
+}
@@ -1,16 +1,38 @@
 //! A proportional-integral-derivative (PID) controller.
+//!
+//! Gains are in the parallel form, where the three terms are independent:
+//! `u = kp * e + ki * ∫e dt + kd * de/dt`. The ideal (or standard) form
+//! `u = kp * (e + 1/ti * ∫e dt + td * de/dt)` converts with `PidCtrl::from_ideal_form` and
+//! `PidCtrl::to_ideal_form`. The series form `kp' * (1 + 1/(ti' s)) * (1 + td' s)` has no
+//! helper; it equals the ideal form with `kp = kp' * (ti' + td') / ti'`, `ti = ti' + td'` and
+//! `td = ti' * td' / (ti' + td')`.
 #![no_std]
+#[cfg(feature = "std")]
+extern crate std;
 use num_traits::{float::FloatCore};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+pub mod analysis;
+#[cfg(feature = "fixed-point")]
+pub mod fixed_point;
+mod math;
+pub mod stability_margin;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
 // #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display, Default)]
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum PidError {
     LimitOutBound,
+    InvalidValue,
+    VersionMismatch,
 }
 
-#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug)]
+/// A closed interval `[lower, upper]` that values are clamped to. The bounds need not be
+/// symmetric about zero: e.g. a heater that can only deliver 0..100 W uses
+/// `Limits::try_new(0.0, 100.0)` or `set_asymmetric_limit(0.0, 100.0)`.
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Limits<T: FloatCore + core::default::Default> {
     lower: T,
@@ -22,17 +44,108 @@ impl<T: FloatCore + core::default::Default> Limits<T> {
         Limits{lower: T::neg_infinity(), upper: T::infinity()}
     }
 
+    /// Creates limits spanning `[lower, upper]`.
+    pub fn try_new(lower: T, upper: T) -> Result<Self, PidError> {
+        if lower.is_nan() || upper.is_nan() {
+            return Err(PidError::InvalidValue);
+        }
+        if lower > upper {
+            return Err(PidError::LimitOutBound);
+        }
+        Ok(Limits{lower, upper})
+    }
+
+    /// Creates limits spanning `[center - half_width, center + half_width]`.
+    pub fn try_from_center(center: T, half_width: T) -> Result<Self, PidError> {
+        if center.is_nan() || half_width.is_nan() || half_width < T::zero() {
+            return Err(PidError::InvalidValue);
+        }
+        Ok(Limits{lower: center - half_width, upper: center + half_width})
+    }
+
+    pub fn lower(&self) -> T {
+        self.lower
+    }
+
+    pub fn upper(&self) -> T {
+        self.upper
+    }
+
+    /// Midpoint of the limits. Not meaningful when either bound is infinite.
+    pub fn center(&self) -> T {
+        (self.lower + self.upper) / (T::one() + T::one())
+    }
+
+    /// Half the distance between the bounds, or `None` if either bound is infinite.
+    pub fn half_width(&self) -> Option<T> {
+        if self.lower.is_infinite() || self.upper.is_infinite() {
+            return None;
+        }
+        Some((self.upper - self.lower) / (T::one() + T::one()))
+    }
+
     fn clamp(&self, val: T) -> T {
+        #[cfg(debug_assertions)]
+        self.assert_valid();
         val.min(self.upper).max(self.lower)
     }
 
+    /// Clamps `val` like the terms do, and reports whether it was outside the limits.
+    /// A NaN `val` counts as outside.
+    pub fn clamp_with_flag(&self, val: T) -> (T, bool) {
+        (self.clamp(val), !(self.lower <= val && val <= self.upper))
+    }
+
+    /// `true` when neither bound is NaN and `lower <= upper`.
+    pub fn is_valid(&self) -> bool {
+        self.lower <= self.upper
+    }
+
+    /// Panics with a description of the broken invariant if `is_valid` is `false`.
+    /// Checked on every clamp in debug builds.
+    pub fn assert_valid(&self) {
+        assert!(!self.lower.is_nan(), "Limits: lower bound is NaN");
+        assert!(!self.upper.is_nan(), "Limits: upper bound is NaN");
+        assert!(self.lower <= self.upper, "Limits: lower bound exceeds upper bound");
+    }
+
+    /// Checked `clamp`: NaN and infinite values are rejected with `PidError::InvalidValue`
+    /// instead of being passed through or pinned to a bound.
+    pub fn try_clamp_finite(&self, val: T) -> Result<T, PidError> {
+        if !val.is_finite() {
+            return Err(PidError::InvalidValue);
+        }
+        Ok(self.clamp(val))
+    }
+
+    /// Sets the symmetric limits `[-|val|, |val|]`; see `set_asymmetric_limit` otherwise.
     pub fn set_limit(&mut self, val: T) -> &mut Self {
         self.lower = -val.abs();
         self.upper = val.abs();
         self
     }
 
+    /// Like `set_limit`, but fails with `PidError::InvalidValue` unless `val` is positive
+    /// instead of taking its absolute value.
+    pub fn try_set_limit(&mut self, val: T) -> Result<&mut Self, PidError> {
+        if val.is_nan() || val <= T::zero() {
+            return Err(PidError::InvalidValue);
+        }
+        Ok(self.set_limit(val))
+    }
+
+    /// Sets both bounds at once, validated like `try_new`. Unlike chaining `try_set_lower` and
+    /// `try_set_upper`, the result does not depend on the current bounds; on error the
+    /// limits are left unchanged.
+    pub fn set_asymmetric_limit(&mut self, lower: T, upper: T) -> Result<&mut Self, PidError> {
+        *self = Limits::try_new(lower, upper)?;
+        Ok(self)
+    }
+
     pub fn try_set_upper(&mut self, val: T) -> Result<&mut Self, PidError> {
+        if val.is_nan() {
+            return Err(PidError::InvalidValue);
+        }
         if self.lower <= val {
             self.upper = val;
             Ok(self)
@@ -43,6 +156,9 @@ impl<T: FloatCore + core::default::Default> Limits<T> {
     }
 
     pub fn try_set_lower(&mut self, val: T) -> Result<&mut Self, PidError> {
+        if val.is_nan() {
+            return Err(PidError::InvalidValue);
+        }
         if self.upper >= val {
             self.lower = val;
             Ok(self)
@@ -51,6 +167,63 @@ impl<T: FloatCore + core::default::Default> Limits<T> {
             Err(PidError::LimitOutBound)
         }
     }
+
+    /// `true` when both bounds are still at their unbounded defaults, `-inf` and `+inf`,
+    /// i.e. the limits were never configured.
+    pub fn is_default(&self) -> bool {
+        self.lower.is_infinite() && self.lower.is_sign_negative()
+            && self.upper.is_infinite() && self.upper.is_sign_positive()
+    }
+
+    /// Returns `true` if the two intervals share at least one point.
+    pub fn overlaps(&self, other: &Limits<T>) -> bool {
+        self.lower <= other.upper && other.lower <= self.upper
+    }
+
+    /// Returns `true` if `self` lies entirely within `other`.
+    pub fn is_subset_of(&self, other: &Limits<T>) -> bool {
+        other.lower <= self.lower && self.upper <= other.upper
+    }
+
+    /// Moves both bounds by `delta`, keeping the width. A NaN `delta` is ignored.
+    pub fn shift(&mut self, delta: T) -> &mut Self {
+        if !delta.is_nan() {
+            self.lower = self.lower + delta;
+            self.upper = self.upper + delta;
+        }
+        self
+    }
+
+    /// Widens the limits by `delta` on each side. A negative `delta` narrows them, at most
+    /// down to the center point. A NaN `delta` is ignored.
+    pub fn expand_symmetric(&mut self, delta: T) -> &mut Self {
+        if !delta.is_nan() {
+            let (lower, upper) = (self.lower - delta, self.upper + delta);
+            if lower > upper {
+                let center = self.center();
+                self.lower = center;
+                self.upper = center;
+            } else {
+                self.lower = lower;
+                self.upper = upper;
+            }
+        }
+        self
+    }
+
+    /// Multiplies both bounds by `factor`, swapping them when it is negative. A zero factor
+    /// gives `[0, 0]`, even for infinite bounds; a NaN or infinite factor is propagated as is.
+    pub fn scale_by(&self, factor: T) -> Self {
+        if factor == T::zero() {
+            return Limits{lower: T::zero(), upper: T::zero()};
+        }
+        let (lower, upper) = (self.lower * factor, self.upper * factor);
+        if factor < T::zero() {
+            Limits{lower: upper, upper: lower}
+        } else {
+            Limits{lower, upper}
+        }
+    }
 }
 
 impl<T: FloatCore + core::default::Default> Default for Limits<T> {
@@ -59,209 +232,3548 @@ impl<T: FloatCore + core::default::Default> Default for Limits<T> {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+/// Formats as `Limits[lower, upper]`, e.g. `Limits[-inf, inf]`.
+impl<T: FloatCore + core::default::Default + core::fmt::Debug> core::fmt::Debug for Limits<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Limits[{:?}, {:?}]", self.lower, self.upper)
+    }
+}
+
+/// Formats as `[lower, upper]`, e.g. `[-5, 10]`, with infinite bounds shown as `-∞` and
+/// `+∞`. Format options such as precision apply to both bounds: `{:.2}` gives `[-5.00, 10.00]`.
+impl<T: FloatCore + core::default::Default + core::fmt::Display> core::fmt::Display for Limits<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let bound = |val: T, f: &mut core::fmt::Formatter<'_>| {
+            if val.is_infinite() {
+                f.write_str(if val < T::zero() { "-∞" } else { "+∞" })
+            } else {
+                core::fmt::Display::fmt(&val, f)
+            }
+        };
+        f.write_str("[")?;
+        bound(self.lower, f)?;
+        f.write_str(", ")?;
+        bound(self.upper, f)?;
+        f.write_str("]")
+    }
+}
+
+/// `Limits::from(..)` is unbounded, the same as `Limits::default()`.
+impl<T: FloatCore + core::default::Default> From<core::ops::RangeFull> for Limits<T> {
+    fn from(_: core::ops::RangeFull) -> Self {
+        Self::new()
+    }
+}
+
+// Hashes the decoded bounds with `-0.0` folded into `0.0`, so that limits which compare
+// equal hash equally. `Limits` is not `Eq` because of NaN, but valid limits never hold NaN.
+impl<T: FloatCore + core::default::Default> core::hash::Hash for Limits<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        for bound in [self.lower, self.upper] {
+            let bound = if bound == T::zero() { T::zero() } else { bound };
+            bound.integer_decode().hash(state);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum TermKind {
+    P,
+    I,
+    D,
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct KPTerm<T: FloatCore + core::default::Default> {
     pub limits: Limits<T>,
     scale: T,
+    setpoint_weight: T,
+}
+
+impl<T: FloatCore + core::default::Default> Default for KPTerm<T> {
+    /// Zero scale, so the term outputs zero, unbounded limits and a setpoint weight of one.
+    fn default() -> Self {
+        KPTerm{limits: Limits::new(), scale: T::zero(), setpoint_weight: T::one()}
+    }
 }
 
 impl<T:FloatCore + core::default::Default> KPTerm<T> {
     pub fn new() -> Self {
         KPTerm::default()
     }
-    pub fn set_scale(&mut self, val: T) -> &mut Self {
-        self.scale = val;
-        self
+    /// Sets the setpoint weight `b` of the two-degree-of-freedom form, where `PidCtrl`
+    /// feeds `b * setpoint - measurement` to the proportional term. One (the default) is
+    /// P-on-error, zero is P-on-measurement. `b` must lie in `[0, 1]`.
+    pub fn set_setpoint_weight(&mut self, b: T) -> Result<&mut Self, PidError> {
+        if !(b >= T::zero() && b <= T::one()) {
+            return Err(PidError::InvalidValue);
+        }
+        self.setpoint_weight = b;
+        Ok(self)
     }
     pub fn step(&self, offset: T) -> T {
-        self.limits.clamp(self.scale * offset)
+        self.limits.clamp(self.step_raw(offset))
+    }
+    // the contribution before `limits`
+    fn step_raw(&self, offset: T) -> T {
+        self.scale * offset
     }
 }
 
-#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+/// Which sign of error `KITerm` integrates. Errors of the other sign hold the accumulator.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum IntegrationSign {
+    #[default]
+    BothSigns,
+    PositiveOnly,
+    NegativeOnly,
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct KITerm<T: FloatCore + core::default::Default> {
     pub limits: Limits<T>,
     scale: T,
-    pub accumulate: T
+    #[deprecated(since = "0.1.5", note = "use `accumulate()` and `set_accumulate()`; the field will become private")]
+    pub accumulate: T,
+    decay_rate: T,
+    anti_windup: bool,
+    max_step: T,
+    integration_sign: IntegrationSign,
+    split_accumulation: bool,
+    accumulate_positive: T,
+    accumulate_negative: T,
+    raw_accumulate: T,
+    integrated_time: T,
+    output_tracking_gain: T,
+    term_tracking_gain: T,
+}
+
+// `accumulate` and `prev_measurement` are deprecated for outside users only; the
+// `allow`s on the term impls go away once the fields are made private.
+#[allow(deprecated)]
+impl<T: FloatCore + core::default::Default> Default for KITerm<T> {
+    /// Zero scale and accumulator, unbounded limits, and no decay, anti-windup, step cap,
+    /// split accumulation or back-calculation correction. The term outputs zero until a scale is set.
+    fn default() -> Self {
+        KITerm{
+            limits: Limits::new(), scale: T::zero(), accumulate: T::zero(), decay_rate: T::zero(),
+            anti_windup: false, max_step: T::infinity(), integration_sign: IntegrationSign::BothSigns,
+            split_accumulation: false, accumulate_positive: T::zero(), accumulate_negative: T::zero(),
+            raw_accumulate: T::zero(), integrated_time: T::zero(), output_tracking_gain: T::zero(),
+            term_tracking_gain: T::zero(),
+        }
+    }
 }
 
+#[allow(deprecated)]
 impl<T:FloatCore + core::default::Default> KITerm<T> {
     pub fn new() -> Self {
         KITerm::default()
     }
-    pub fn set_scale(&mut self, val: T) -> &mut Self {
-        self.scale = val;
+    /// Sets the rate (per unit time) at which the accumulator decays towards zero:
+    /// each step multiplies it by `exp(-rate * tdelta)`. Zero, the default, disables decay.
+    pub fn set_decay_rate(&mut self, rate: T) -> Result<&mut Self, PidError> {
+        if !rate.is_finite() || rate < T::zero() {
+            return Err(PidError::InvalidValue);
+        }
+        self.decay_rate = rate;
+        Ok(self)
+    }
+    /// With anti-windup enabled, an error whose sign opposes the accumulator (e.g. on
+    /// overshoot) is not integrated: the accumulator holds its value for that step rather
+    /// than being reset, so it only ever moves towards its steady-state value while the error
+    /// agrees with it. `sign(0)` is taken as no sign: a zero error or a zero accumulator
+    /// never opposes the other, and is integrated as usual.
+    pub fn set_anti_windup(&mut self, enabled: bool) -> &mut Self {
+        self.anti_windup = enabled;
+        self
+    }
+    /// Caps the change `scale * offset * tdelta` added to the accumulator in a single
+    /// step to `±max_step`, guarding against a huge `tdelta` after an idle period.
+    /// Must be positive; infinite, the default, disables the cap.
+    pub fn set_max_step(&mut self, max_step: T) -> Result<&mut Self, PidError> {
+        if max_step.is_nan() || max_step <= T::zero() {
+            return Err(PidError::InvalidValue);
+        }
+        self.max_step = max_step;
+        Ok(self)
+    }
+    /// Per-step fraction of the output saturation error `apply_back_calc_correction` feeds
+    /// back into the accumulator. Must be non-negative and finite; zero, the default,
+    /// disables it.
+    pub fn set_output_tracking_gain(&mut self, gain: T) -> Result<&mut Self, PidError> {
+        if !gain.is_finite() || gain < T::zero() {
+            return Err(PidError::InvalidValue);
+        }
+        self.output_tracking_gain = gain;
+        Ok(self)
+    }
+    /// Per-step fraction of this term's own saturation error `apply_back_calc_correction`
+    /// feeds back into the accumulator. Must be non-negative and finite; zero, the default,
+    /// disables it.
+    pub fn set_term_tracking_gain(&mut self, gain: T) -> Result<&mut Self, PidError> {
+        if !gain.is_finite() || gain < T::zero() {
+            return Err(PidError::InvalidValue);
+        }
+        self.term_tracking_gain = gain;
+        Ok(self)
+    }
+    pub fn output_tracking_gain(&self) -> T {
+        self.output_tracking_gain
+    }
+    pub fn term_tracking_gain(&self) -> T {
+        self.term_tracking_gain
+    }
+    /// Adds `output_tracking_gain * output_saturation_error + term_tracking_gain *
+    /// per_term_saturation_error` to the accumulator, clamped to `limits`. Each error is
+    /// `saturated - unsaturated`: of the controller output for the first, of this term
+    /// (`accumulate() - raw_accumulate()`) for the second, so saturation against either the
+    /// output limits or a term limit alone pulls the integrator back. `PidCtrl` calls this
+    /// after every automatic step.
+    pub fn apply_back_calc_correction(&mut self, output_saturation_error: T, per_term_saturation_error: T) -> &mut Self {
+        let correction = self.output_tracking_gain * output_saturation_error
+            + self.term_tracking_gain * per_term_saturation_error;
+        if correction != T::zero() {
+            self.accumulate = self.limits.clamp(self.accumulate + correction);
+            self.split_accumulate();
+        }
+        self
+    }
+    pub fn set_integration_sign(&mut self, sign: IntegrationSign) -> &mut Self {
+        self.integration_sign = sign;
+        self
+    }
+    pub fn accumulate(&self) -> T {
+        self.accumulate
+    }
+    pub fn set_accumulate(&mut self, val: T) -> &mut Self {
+        self.accumulate = val;
+        self.raw_accumulate = val;
+        self.split_accumulate();
+        self
+    }
+    /// Keeps positive and negative increments in separate accumulators, clamped to
+    /// `[0, upper]` and `[lower, 0]` of `limits` respectively, and returns their sum. Errors
+    /// of one sign then cannot unwind what the other sign accumulated beyond its own bound.
+    pub fn set_split_accumulation(&mut self, enabled: bool) -> &mut Self {
+        self.split_accumulation = enabled;
+        self.split_accumulate();
         self
     }
+    /// `true` when the accumulator is within `proximity_fraction` of the limit width from
+    /// either bound, e.g. `0.05` for "within 5%". A sustained `true` suggests the integrator
+    /// is saturated. Always `false` with an infinite bound.
+    pub fn is_near_limit(&self, proximity_fraction: T) -> bool {
+        let width = match self.limits.half_width() {
+            Some(half_width) => half_width + half_width,
+            None => return false,
+        };
+        if width == T::zero() {
+            return true;
+        }
+        let distance = (self.accumulate - self.limits.lower).min(self.limits.upper - self.accumulate);
+        distance / width < proximity_fraction
+    }
+    /// The accumulator as the last `step` computed it, before `limits` were applied;
+    /// `accumulate() == limits.clamp(raw_accumulate())` unless `apply_back_calc_correction`
+    /// has moved the accumulator since.
+    pub fn raw_accumulate(&self) -> T {
+        self.raw_accumulate
+    }
+    /// How far the last `step` pushed the accumulator past its limits: positive when
+    /// wound up against the upper bound, negative against the lower one, zero otherwise.
+    pub fn windup_amount(&self) -> T {
+        self.raw_accumulate - self.accumulate
+    }
+    /// Sum of the `tdelta`s integrated since construction or the last `PidCtrl::reset`,
+    /// e.g. for adaptive gains or to spot windup from long step periods. Includes steps in
+    /// manual mode, during which the accumulator tracks the manual output.
+    pub fn integrated_time(&self) -> T {
+        self.integrated_time
+    }
+    pub fn accumulate_positive(&self) -> T {
+        self.accumulate_positive
+    }
+    pub fn accumulate_negative(&self) -> T {
+        self.accumulate_negative
+    }
+    fn split_accumulate(&mut self) {
+        self.accumulate_positive = self.accumulate.max(T::zero());
+        self.accumulate_negative = self.accumulate.min(T::zero());
+    }
+    fn copy_state_from(&mut self, other: &KITerm<T>) {
+        self.accumulate = other.accumulate;
+        self.raw_accumulate = other.raw_accumulate;
+        self.accumulate_positive = other.accumulate_positive;
+        self.accumulate_negative = other.accumulate_negative;
+        self.integrated_time = other.integrated_time;
+    }
     pub fn step(&mut self, offset: T, tdelta: T) -> T {
-        let i = self.limits.clamp(self.scale * offset * tdelta + self.accumulate);
-        self.accumulate = i;
-        i
+        self.step_with_back_calc(offset, tdelta, T::zero())
+    }
+    /// Integrates `scale * offset + back_calc_correction` over `tdelta`. The correction is
+    /// the back-calculation anti-windup rate `tracking_gain * (saturated_out - unsaturated_out)`
+    /// computed by `PidCtrl`; it is not subject to `IntegrationSign`.
+    pub fn step_with_back_calc(&mut self, offset: T, tdelta: T, back_calc_correction: T) -> T {
+        self.step_raw(offset, tdelta, back_calc_correction);
+        self.accumulate
+    }
+    /// Conditional integration: like `step`, but when the new accumulator would fall outside
+    /// `limits` the step is skipped and the accumulator held where it is, rather than
+    /// snapped to the bound. Only the integrated time advances on a skipped step.
+    pub fn step_conditional_clamp(&mut self, offset: T, tdelta: T) -> T {
+        let mut proposed = *self;
+        let raw = proposed.step_raw(offset, tdelta, T::zero());
+        if self.limits.clamp_with_flag(raw).1 {
+            self.integrated_time = self.integrated_time + tdelta;
+        } else {
+            *self = proposed;
+        }
+        self.accumulate
+    }
+    // updates the accumulator and returns it before `limits`, i.e. `raw_accumulate`
+    fn step_raw(&mut self, offset: T, tdelta: T, back_calc_correction: T) -> T {
+        if self.split_accumulation && self.accumulate_positive + self.accumulate_negative != self.accumulate {
+            // the accumulator was written directly
+            self.split_accumulate();
+        }
+        let mut accumulate = self.accumulate;
+        self.integrated_time = self.integrated_time + tdelta;
+        if self.decay_rate != T::zero() {
+            let decay = math::exp(-self.decay_rate * tdelta);
+            accumulate = accumulate * decay;
+            self.accumulate_positive = self.accumulate_positive * decay;
+            self.accumulate_negative = self.accumulate_negative * decay;
+        }
+        let opposes = offset != T::zero() && accumulate != T::zero()
+            && (offset > T::zero()) != (accumulate > T::zero());
+        let integrate = !(self.anti_windup && opposes) && match self.integration_sign {
+            IntegrationSign::BothSigns => true,
+            IntegrationSign::PositiveOnly => offset > T::zero(),
+            IntegrationSign::NegativeOnly => offset < T::zero(),
+        };
+        let rate = if integrate { self.scale * offset } else { T::zero() };
+        let delta = ((rate + back_calc_correction) * tdelta).max(-self.max_step).min(self.max_step);
+        self.raw_accumulate = if self.split_accumulation {
+            if delta > T::zero() {
+                self.accumulate_positive = (self.accumulate_positive + delta).min(self.limits.upper.max(T::zero()));
+            } else {
+                self.accumulate_negative = (self.accumulate_negative + delta).max(self.limits.lower.min(T::zero()));
+            }
+            self.accumulate_positive + self.accumulate_negative
+        } else {
+            delta + accumulate
+        };
+        self.accumulate = self.limits.clamp(self.raw_accumulate);
+        self.raw_accumulate
     }
 }
 
-#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+/// Finite-difference stencil `KDTerm` uses for the derivative of the measurement. The
+/// central stencils assume a constant `tdelta` and, being causal, estimate the derivative
+/// at the middle of their window: one sample ago for `ThreePointCentral`, two for
+/// `FivePointCentral`. In exchange they are exact for polynomials of degree two and four.
+/// `ThreePointBackward` is exact for quadratics at the current sample.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum DiffStencil {
+    /// `(m[k] - m[k-1]) / tdelta`, the default.
+    #[default]
+    TwoPoint,
+    /// `(m[k] - m[k-2]) / (2 * tdelta)`.
+    ThreePointCentral,
+    /// `(3 * m[k] - 4 * m[k-1] + m[k-2]) / (2 * tdelta)`, second-order accurate at the
+    /// current sample, so without the lag of the central stencils.
+    ThreePointBackward,
+    /// `(m[k-4] - 8 * m[k-3] + 8 * m[k-1] - m[k]) / (12 * tdelta)`.
+    FivePointCentral,
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct KDTerm<T: FloatCore + core::default::Default> {
     pub limits: Limits<T>,
     scale: T,
-    pub prev_measurement: T
+    #[deprecated(
+        since = "0.1.5",
+        note = "use `prev_measurement()` and `set_prev_measurement()`; the field will become private"
+    )]
+    pub prev_measurement: T,
+    ewma_alpha: T,
+    initial_derivative: Option<T>,
+    second_order: bool,
+    // the (filtered) measurements before `prev_measurement`, most recent first
+    older_measurements: [T; 3],
+    stencil: DiffStencil,
+    decimation: u32,
+    // steps since the derivative was last computed, modulo `decimation`
+    step_count: u32,
+    // time stepped over since then and the value held meanwhile
+    held_time: T,
+    held_derivative: T,
+}
+
+#[allow(deprecated)]
+impl<T: FloatCore + core::default::Default> Default for KDTerm<T> {
+    /// Zero scale and history, unbounded limits, no filtering or decimation and the
+    /// two-point stencil.
+    /// The term outputs zero until a scale is set.
+    fn default() -> Self {
+        KDTerm{
+            limits: Limits::new(), scale: T::zero(), prev_measurement: T::zero(), ewma_alpha: T::one(),
+            initial_derivative: None, second_order: false, older_measurements: [T::zero(); 3],
+            stencil: DiffStencil::TwoPoint, decimation: 1, step_count: 0, held_time: T::zero(),
+            held_derivative: T::zero(),
+        }
+    }
 }
 
+#[allow(deprecated)]
 impl<T:FloatCore + core::default::Default> KDTerm<T> {
     pub fn new() -> Self {
         KDTerm::default()
     }
-    pub fn set_scale(&mut self, val: T) -> &mut Self {
-        self.scale = val;
+    /// A derivative term discretized with the bilinear (Tustin) transform instead of a
+    /// backward difference; see `BilinearKDTerm`. Panics unless `scale` is finite and
+    /// `sample_period` positive and finite.
+    pub fn new_tustin(scale: T, sample_period: T) -> BilinearKDTerm<T> {
+        BilinearKDTerm::try_new(scale, sample_period)
+            .expect("KDTerm::new_tustin: scale must be finite and sample_period positive")
+    }
+    /// Smooths measurements with an exponentially weighted moving average before
+    /// differentiating: `filtered = alpha * measurement + (1 - alpha) * prev_measurement`.
+    /// `prev_measurement` then holds the filtered value. `alpha` must lie in `(0, 1]`;
+    /// the default of one disables the filter.
+    pub fn set_ewma_alpha(&mut self, alpha: T) -> Result<&mut Self, PidError> {
+        if !(alpha > T::zero() && alpha <= T::one()) {
+            return Err(PidError::InvalidValue);
+        }
+        self.ewma_alpha = alpha;
+        Ok(self)
+    }
+    /// Preloads the rate of change of the measurement (e.g. from a tachometer). The next
+    /// `step` returns `limits.clamp(-scale * deriv)`, the same sign convention as the
+    /// finite difference, instead of differencing against `prev_measurement`.
+    pub fn set_initial_derivative(&mut self, deriv: T) -> &mut Self {
+        self.initial_derivative = Some(deriv);
         self
     }
-    pub fn step(&mut self, measurement: T, tdelta: T) -> T {
-        let d = self.limits.clamp(self.scale * (self.prev_measurement - measurement) / tdelta);
+    /// Switches to the second difference of the measurement,
+    /// `-scale * (measurement - 2 * prev_measurement + prev_prev_measurement) / tdelta^2`,
+    /// for jerk-limited motion. The sign follows the first-order form, which is the
+    /// derivative of the error for a fixed setpoint. Takes precedence over the stencil.
+    pub fn set_second_order(&mut self, enabled: bool) -> &mut Self {
+        self.second_order = enabled;
+        self
+    }
+    /// Selects the finite-difference stencil for the first derivative (see `DiffStencil`).
+    pub fn set_stencil(&mut self, stencil: DiffStencil) -> &mut Self {
+        self.stencil = stencil;
+        self
+    }
+    /// Computes the derivative only on every `n`th step and holds the last value in
+    /// between, differencing over the whole `n` steps so the noise of fast sampling is
+    /// averaged out. The measurement history only advances on computing steps. Zero is
+    /// treated as one, the default, which computes every step.
+    pub fn set_decimation_factor(&mut self, n: u32) -> &mut Self {
+        self.decimation = n.max(1);
+        self.step_count = 0;
+        self.held_time = T::zero();
+        self
+    }
+    pub fn prev_measurement(&self) -> T {
+        self.prev_measurement
+    }
+    pub fn set_prev_measurement(&mut self, val: T) -> &mut Self {
+        self.prev_measurement = val;
+        self
+    }
+    // Seeds the measurement history so the first step sees no change.
+    fn seed(&mut self, measurement: T) {
         self.prev_measurement = measurement;
-        d
+        self.older_measurements = [measurement; 3];
+        self.step_count = 0;
+        self.held_time = T::zero();
+    }
+    fn copy_state_from(&mut self, other: &KDTerm<T>) {
+        self.prev_measurement = other.prev_measurement;
+        self.older_measurements = other.older_measurements;
+        self.initial_derivative = other.initial_derivative;
+        self.step_count = other.step_count;
+        self.held_time = other.held_time;
+        self.held_derivative = other.held_derivative;
+    }
+    pub fn step(&mut self, measurement: T, tdelta: T) -> T {
+        let raw = self.step_raw(measurement, tdelta);
+        self.limits.clamp(raw)
+    }
+    // the contribution before `limits`
+    fn step_raw(&mut self, measurement: T, tdelta: T) -> T {
+        if self.decimation > 1 {
+            let phase = self.step_count;
+            self.step_count = (self.step_count + 1) % self.decimation;
+            if phase != 0 {
+                self.held_time = self.held_time + tdelta;
+                return self.held_derivative;
+            }
+        }
+        let tdelta = tdelta + self.held_time;
+        self.held_time = T::zero();
+        let filtered = if self.ewma_alpha == T::one() {
+            measurement
+        } else {
+            self.ewma_alpha * measurement + (T::one() - self.ewma_alpha) * self.prev_measurement
+        };
+        let prev = self.prev_measurement;
+        let [prev2, prev3, prev4] = self.older_measurements;
+        let two = T::one() + T::one();
+        let raw = match self.initial_derivative.take() {
+            Some(deriv) => -self.scale * deriv,
+            None if self.second_order => self.scale * (prev + prev - filtered - prev2) / (tdelta * tdelta),
+            None => match self.stencil {
+                DiffStencil::TwoPoint => self.scale * (prev - filtered) / tdelta,
+                DiffStencil::ThreePointCentral => self.scale * (prev2 - filtered) / (two * tdelta),
+                DiffStencil::ThreePointBackward => self.scale
+                    * (two * two * prev - math::cast::<T>(3.0) * filtered - prev2)
+                    / (two * tdelta),
+                DiffStencil::FivePointCentral => self.scale
+                    * (filtered - prev4 + math::cast::<T>(8.0) * (prev3 - prev))
+                    / (math::cast::<T>(12.0) * tdelta),
+            },
+        };
+        self.older_measurements = [prev, prev2, prev3];
+        self.prev_measurement = filtered;
+        self.held_derivative = raw;
+        raw
     }
 }
 
-#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+/// Derivative term `-scale * s / (1 + tf * s)` on the measurement, discretized with the
+/// bilinear transform `s -> (2 / T) * (z - 1) / (z + 1)` for a fixed sample period `T`:
+///
+/// `y[n] = (-2 * scale * (x[n] - x[n-1]) - (T - 2 * tf) * y[n-1]) / (T + 2 * tf)`
+///
+/// The bilinear transform avoids the frequency warping of `KDTerm`'s backward difference.
+/// With the default filter time constant `tf = T / 2` the recursion reduces exactly to that
+/// backward difference, `-scale * (x[n] - x[n-1]) / T`; a larger `tf` low-passes the
+/// derivative. `tf = 0`, the pure bilinear derivative, rings at the Nyquist frequency.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
-pub struct PidCtrl <T: FloatCore + core::default::Default> {
-    pub kp: KPTerm<T>,
-    pub ki: KITerm<T>,
-    pub kd: KDTerm<T>,
+pub struct BilinearKDTerm<T: FloatCore + core::default::Default> {
     pub limits: Limits<T>,
-    
-    pub setpoint: T,
+    scale: T,
+    sample_period: T,
+    filter_time_constant: T,
+    prev_input: T,
+    prev_output: T,
 }
 
-impl<T: FloatCore + core::default::Default> PidCtrl<T>
-    {
-        pub fn new() -> Self {
-            PidCtrl::default()
+impl<T: FloatCore + core::default::Default> BilinearKDTerm<T> {
+    /// `scale` must be finite and `sample_period` positive and finite.
+    pub fn try_new(scale: T, sample_period: T) -> Result<Self, PidError> {
+        if !scale.is_finite() || !sample_period.is_finite() || sample_period <= T::zero() {
+            return Err(PidError::InvalidValue);
+        }
+        Ok(BilinearKDTerm{
+            limits: Limits::new(), scale, sample_period,
+            filter_time_constant: sample_period / (T::one() + T::one()),
+            prev_input: T::zero(), prev_output: T::zero(),
+        })
+    }
+    pub fn scale(&self) -> T {
+        self.scale
+    }
+    pub fn sample_period(&self) -> T {
+        self.sample_period
+    }
+    pub fn filter_time_constant(&self) -> T {
+        self.filter_time_constant
+    }
+    /// Must be non-negative and finite.
+    pub fn set_filter_time_constant(&mut self, tf: T) -> Result<&mut Self, PidError> {
+        if !tf.is_finite() || tf < T::zero() {
+            return Err(PidError::InvalidValue);
         }
+        self.filter_time_constant = tf;
+        Ok(self)
+    }
+    pub fn prev_input(&self) -> T {
+        self.prev_input
+    }
+    /// The last output, before `limits` were applied.
+    pub fn prev_output(&self) -> T {
+        self.prev_output
+    }
+    /// Sets the previous input to `measurement` and clears the filter state, so the next
+    /// step sees no jump.
+    pub fn seed(&mut self, measurement: T) -> &mut Self {
+        self.prev_input = measurement;
+        self.prev_output = T::zero();
+        self
+    }
+    pub fn step(&mut self, measurement: T) -> T {
+        let two_tf = self.filter_time_constant + self.filter_time_constant;
+        let two = T::one() + T::one();
+        let raw = (-two * self.scale * (measurement - self.prev_input)
+            - (self.sample_period - two_tf) * self.prev_output)
+            / (self.sample_period + two_tf);
+        self.prev_input = measurement;
+        self.prev_output = raw;
+        self.limits.clamp(raw)
+    }
+}
 
-        pub fn new_with_pid(p: T, i: T, d: T) -> Self {
-            Self{
-                kp: KPTerm{limits:Limits::new(), scale: p}, 
-                ki: KITerm{limits:Limits::new(), scale: i, accumulate:T::zero()}, 
-                kd: KDTerm{limits:Limits::new(), scale: d, prev_measurement:T::zero()}, 
-                limits: Limits::new(), setpoint: T::zero(),
+/// Common interface over `KPTerm`, `KITerm` and `KDTerm`.
+///
+/// `set_scale_checked` returns `()` rather than `&mut Self` so the trait stays usable as
+/// `dyn PidTerm<T>`; use the inherent `set_scale` for chaining.
+pub trait PidTerm<T: FloatCore + core::default::Default> {
+    fn scale(&self) -> T;
+    /// Sets the scale, rejecting NaN and infinite values with `PidError::InvalidValue`.
+    fn set_scale_checked(&mut self, val: T) -> Result<(), PidError>;
+    fn limits(&self) -> &Limits<T>;
+    fn limits_mut(&mut self) -> &mut Limits<T>;
+}
+
+macro_rules! impl_pid_term {
+    ($term:ident) => {
+        impl<T: FloatCore + core::default::Default> $term<T> {
+            /// Sets the scale. A NaN or infinite value panics in debug builds; release builds
+            /// store zero for NaN and saturate infinities to `±T::max_value()`.
+            pub fn set_scale(&mut self, val: T) -> &mut Self {
+                debug_assert!(val.is_finite(), "scale must be finite");
+                self.scale = if val.is_nan() {
+                    T::zero()
+                } else {
+                    val.max(T::min_value()).min(T::max_value())
+                };
+                self
+            }
+            /// Sets the scale, rejecting NaN and infinite values with `PidError::InvalidValue`.
+            pub fn try_set_scale(&mut self, val: T) -> Result<&mut Self, PidError> {
+                if !val.is_finite() {
+                    return Err(PidError::InvalidValue);
+                }
+                self.scale = val;
+                Ok(self)
             }
         }
 
-        pub fn init(&mut self, setpoint: T, prev_measurement: T) -> &mut Self {
-            self.setpoint = setpoint;
-            self.kd.prev_measurement = prev_measurement;
-            self
+        impl<T: FloatCore + core::default::Default> PidTerm<T> for $term<T> {
+            fn scale(&self) -> T {
+                self.scale
+            }
+            fn set_scale_checked(&mut self, val: T) -> Result<(), PidError> {
+                self.try_set_scale(val).map(|_| ())
+            }
+            fn limits(&self) -> &Limits<T> {
+                &self.limits
+            }
+            fn limits_mut(&mut self) -> &mut Limits<T> {
+                &mut self.limits
+            }
         }
+    };
+}
 
-        pub fn step(&mut self, input: PidIn<T>) -> PidOut<T> {
-            let offset = self.setpoint - input.measurement;
-            let p = self.kp.step(offset);
-            let i = self.ki.step(offset, input.tdelta);
-            let d = self.kd.step(input.measurement, input.tdelta);
-            PidOut::new(p, i, d, self.limits.clamp(p + i + d))
+impl_pid_term!(KPTerm);
+impl_pid_term!(KITerm);
+impl_pid_term!(KDTerm);
+
+/// Running statistics of the error signal, updated with Welford's online algorithm.
+/// `variance` is the population variance.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ErrorStats<T: FloatCore + core::default::Default> {
+    pub mean: T,
+    pub variance: T,
+    pub min: T,
+    pub max: T,
+    pub count: u64,
+    m2: T,
+}
+
+impl<T: FloatCore + core::default::Default> ErrorStats<T> {
+    fn update(&mut self, error: T) {
+        self.count += 1;
+        let count = T::from(self.count).unwrap();
+        let delta = error - self.mean;
+        self.mean = self.mean + delta / count;
+        self.m2 = self.m2 + delta * (error - self.mean);
+        self.variance = self.m2 / count;
+        self.min = self.min.min(error);
+        self.max = self.max.max(error);
+    }
+}
+
+impl<T: FloatCore + core::default::Default> Default for ErrorStats<T> {
+    fn default() -> Self {
+        ErrorStats{
+            mean: T::zero(), variance: T::zero(), min: T::infinity(), max: T::neg_infinity(),
+            count: 0, m2: T::zero(),
         }
     }
+}
 
-#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+/// Biquad notch filter removing a band around `center_freq` (in cycles per unit time)
+/// `bandwidth` wide. Coefficients follow the RBJ audio cookbook and are recomputed from
+/// `tdelta` on every call, so irregular sampling is handled.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
-pub struct PidIn <T: FloatCore + core::default::Default> {
-    measurement: T,
-    tdelta: T,
+pub struct NotchFilter<T: FloatCore + core::default::Default> {
+    center_freq: T,
+    bandwidth: T,
+    x1: T,
+    x2: T,
+    y1: T,
+    y2: T,
 }
 
-impl<T: FloatCore + core::default::Default> PidIn<T> {
-        pub fn new(measurement:T, tdelta:T) -> Self {
-            let tdelta_clamped = tdelta.min(T::infinity()).max(T::epsilon());
-            PidIn{measurement, tdelta: tdelta_clamped}
+impl<T: FloatCore + core::default::Default> NotchFilter<T> {
+    /// Both `center_freq` and `bandwidth` must be positive and finite.
+    pub fn try_new(center_freq: T, bandwidth: T) -> Result<Self, PidError> {
+        let valid = |val: T| val.is_finite() && val > T::zero();
+        if !valid(center_freq) || !valid(bandwidth) {
+            return Err(PidError::InvalidValue);
         }
+        Ok(NotchFilter{
+            center_freq, bandwidth, x1: T::zero(), x2: T::zero(), y1: T::zero(), y2: T::zero(),
+        })
+    }
+    pub fn center_freq(&self) -> T {
+        self.center_freq
+    }
+    pub fn bandwidth(&self) -> T {
+        self.bandwidth
+    }
+    /// Sets the filter history as if `val` had been applied forever, so a constant input
+    /// passes through without a transient.
+    pub fn seed(&mut self, val: T) -> &mut Self {
+        self.x1 = val;
+        self.x2 = val;
+        self.y1 = val;
+        self.y2 = val;
+        self
+    }
+    fn copy_state_from(&mut self, other: &NotchFilter<T>) {
+        self.x1 = other.x1;
+        self.x2 = other.x2;
+        self.y1 = other.y1;
+        self.y2 = other.y2;
     }
+    pub fn filter(&mut self, x: T, tdelta: T) -> T {
+        let two = T::one() + T::one();
+        let omega = two * math::cast::<T>(core::f64::consts::PI) * self.center_freq * tdelta;
+        let (sin, cos) = math::sin_cos(omega);
+        let alpha = sin * self.bandwidth / (two * self.center_freq);
+        let a0 = T::one() + alpha;
+        let b1 = -two * cos;
+        let y = (x + b1 * self.x1 + self.x2 - b1 * self.y1 - (T::one() - alpha) * self.y2) / a0;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
 
-#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+/// What `PidCtrl` does with a setpoint outside its setpoint limits.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
-pub struct PidOut <T: FloatCore + core::default::Default> {
-    pub p: T,
-    pub i: T,
-    pub d: T,
-    pub out: T,
+#[non_exhaustive]
+pub enum SetpointLimitMode {
+    /// Steps use the setpoint clamped to the limits.
+    #[default]
+    Clamp,
 }
 
-impl<T: FloatCore + core::default::Default> PidOut<T> {
-        pub fn new(p:T, i:T, d:T, out:T) -> Self {
-            Self{p, i, d, out}
+/// `PidCtrl` is `Copy` because every field is, including the custom error function, which
+/// is a plain `fn` pointer for that reason. New fields must keep it so; state that cannot
+/// be `Copy`, such as a capturing closure, belongs in a wrapper like `PidCtrlFlex`.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct PidCtrl <T: FloatCore + core::default::Default> {
+    pub kp: KPTerm<T>,
+    pub ki: KITerm<T>,
+    pub kd: KDTerm<T>,
+    pub limits: Limits<T>,
+    
+    pub setpoint: T,
+    step_count: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    error_fn: Option<ErrorFn<T>>,
+    error_stats: ErrorStats<T>,
+    warmed_up: bool,
+    manual_output: Option<T>,
+    last_out: PidOut<T>,
+    measurement_filter: Option<NotchFilter<T>>,
+    quantization_step: T,
+    tracking_gain: T,
+    saturation_error: T,
+    integral_reset_threshold: T,
+    last_setpoint: T,
+    time_at_current_setpoint: T,
+    convergence_criteria: Option<(T, T)>,
+    time_within_tolerance: T,
+    sample_period: Option<T>,
+    iae: T,
+    ise: T,
+    setpoint_limits: Option<(Limits<T>, SetpointLimitMode)>,
+}
+
+impl<T: FloatCore + core::default::Default> Default for PidCtrl<T> {
+    /// A zero-gain controller: every term has a scale of zero, so `step` always outputs zero
+    /// until the gains are set with `set_scale` or the controller is built with
+    /// `new_with_pid`. All limits are unbounded and the setpoint is zero.
+    fn default() -> Self {
+        PidCtrl{
+            kp: KPTerm::default(), ki: KITerm::default(), kd: KDTerm::default(), limits: Limits::new(),
+            setpoint: T::zero(), step_count: 0, error_fn: None, error_stats: ErrorStats::default(),
+            warmed_up: false, manual_output: None, last_out: PidOut::default(), measurement_filter: None,
+            quantization_step: T::zero(), tracking_gain: T::zero(), saturation_error: T::zero(),
+            integral_reset_threshold: T::infinity(), last_setpoint: T::zero(),
+            time_at_current_setpoint: T::zero(), convergence_criteria: None, time_within_tolerance: T::zero(),
+            sample_period: None, iae: T::zero(), ise: T::zero(), setpoint_limits: None,
         }
     }
+}
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn limits_error() {
-        let mut pid = super::PidCtrl::new_with_pid(3.0, 2.0, 1.0);
-        pid.kp.limits.try_set_lower(10.0).unwrap();
-        assert_eq!(super::PidError::LimitOutBound, pid.kp.limits.try_set_upper(5.0).unwrap_err());
+// No float type is `Eq` or `Ord`; these are for fixed-point `T`. With a totally ordered `T`
+// every field is totally ordered, so the derived `partial_cmp` never returns `None` and `cmp`
+// can defer to it, which also keeps the two consistent.
+impl<T: FloatCore + core::default::Default + Eq + Ord> Eq for PidCtrl<T> {}
+
+#[allow(clippy::derive_ord_xor_partial_ord)]
+impl<T: FloatCore + core::default::Default + Eq + Ord> Ord for PidCtrl<T> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.partial_cmp(other).expect("fields of a totally ordered T are totally ordered")
     }
+}
 
-    #[test]
-    fn kp() {
-        let kp = 0.2;
-        let measurement = 0.0;
-        let setpoint = 1.0;
+struct ErrorFn<T>(fn(T, T) -> T);
 
-        let mut pid = super::PidCtrl::default();
-        pid.init(setpoint, 0.0);
-        pid.kp.set_scale(kp);
+impl<T> Copy for ErrorFn<T> {}
+
+impl<T> Clone for ErrorFn<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+// Compares by address through `fn_addr_eq`, which is enough to tell a configured error
+// function from the default.
+impl<T> PartialEq for ErrorFn<T> {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::fn_addr_eq(self.0, other.0)
+    }
+}
+
+impl<T> PartialOrd for ErrorFn<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        (self.0 as usize).partial_cmp(&(other.0 as usize))
+    }
+}
+
+impl<T> core::hash::Hash for ErrorFn<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        (self.0 as usize).hash(state);
+    }
+}
+
+impl<T> core::fmt::Debug for ErrorFn<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ErrorFn({:p})", self.0 as *const ())
+    }
+}
+
+fn error_with<T: FloatCore>(error_fn: Option<ErrorFn<T>>, setpoint: T, measurement: T) -> T {
+    match error_fn {
+        Some(ErrorFn(f)) => f(setpoint, measurement),
+        None => setpoint - measurement,
+    }
+}
+
+impl<T: FloatCore + core::default::Default> PidCtrl<T>
+    {
+        /// Layout version written by `to_config`; bump whenever the serialized fields change.
+        pub const VERSION: u32 = 1;
+
+        pub fn new() -> Self {
+            PidCtrl::default()
+        }
+
+        /// Panics if any gain is NaN or infinite.
+        pub fn new_with_pid(p: T, i: T, d: T) -> Self {
+            assert!(
+                p.is_finite() && i.is_finite() && d.is_finite(),
+                "PidCtrl::new_with_pid: gains must be finite",
+            );
+            let mut pid = Self::default();
+            pid.kp.scale = p;
+            pid.ki.scale = i;
+            pid.kd.scale = d;
+            pid
+        }
+
+        /// `new_with_pid` followed by `init`, as a single expression. Panics like `new_with_pid`.
+        pub fn new_with_pid_and_init(p: T, i: T, d: T, setpoint: T, prev_measurement: T) -> Self {
+            let mut pid = Self::new_with_pid(p, i, d);
+            pid.init(setpoint, prev_measurement);
+            pid
+        }
+
+        /// Builds a controller from the standard form `kp * (e + 1/ti * ∫e dt + td * de/dt)`,
+        /// i.e. `ki = kp / ti` and `kd = kp * td`. An infinite `ti` disables the integral.
+        pub fn new_with_standard_form(kp: T, ti: T, td: T) -> Self {
+            Self::new_with_pid(kp, kp / ti, kp * td)
+        }
+
+        /// Same as `new_with_standard_form`; the ideal and standard forms are one and the same.
+        pub fn from_ideal_form(kp: T, ti: T, td: T) -> Self {
+            Self::new_with_standard_form(kp, ti, td)
+        }
+
+        /// Same as `gains_standard_form`: `(kp, ti = kp / ki, td = kd / kp)`.
+        pub fn to_ideal_form(&self) -> (T, T, T) {
+            self.gains_standard_form()
+        }
+
+        /// Returns the gains as `(kp, ti, td)` in standard form. `ti` is infinite when
+        /// `ki` is zero and `td` is zero when `kd` is zero. With `kp` zero, a non-zero `ki`
+        /// or `kd` has no standard-form equivalent and the corresponding value is NaN.
+        pub fn gains_standard_form(&self) -> (T, T, T) {
+            let kp = self.kp.scale;
+            let ti = if self.ki.scale == T::zero() {
+                T::infinity()
+            } else if kp == T::zero() {
+                T::nan()
+            } else {
+                kp / self.ki.scale
+            };
+            let td = if self.kd.scale == T::zero() {
+                T::zero()
+            } else if kp == T::zero() {
+                T::nan()
+            } else {
+                self.kd.scale / kp
+            };
+            (kp, ti, td)
+        }
+
+        /// Continuous-time `(kp, ki, kd)` equivalent to gains tuned per sample, i.e. for a
+        /// loop that feeds `step` a fixed `tdelta` of one. Assumes rectangular integration
+        /// (`ki_cont = ki / sample_period`) and a backward-difference derivative
+        /// (`kd_cont = kd * sample_period`). Loops that pass the real elapsed time as
+        /// `tdelta` already use continuous-time gains and need no conversion.
+        pub fn to_continuous_gains(&self, sample_period: T) -> (T, T, T) {
+            (self.kp.scale, self.ki.scale / sample_period, self.kd.scale * sample_period)
+        }
+
+        /// Rescales per-sample gains (see `to_continuous_gains`) tuned at `old_period` so the
+        /// continuous-time gains are unchanged at `new_period`: `ki` scales with
+        /// `new_period / old_period` and `kd` with `old_period / new_period`.
+        pub fn normalize_to_sample_period(&mut self, new_period: T, old_period: T) -> &mut Self {
+            self.ki.scale = self.ki.scale * new_period / old_period;
+            self.kd.scale = self.kd.scale * old_period / new_period;
+            self
+        }
+
+        pub fn init(&mut self, setpoint: T, prev_measurement: T) -> &mut Self {
+            self.setpoint = setpoint;
+            self.kd.seed(prev_measurement);
+            if let Some(filter) = self.measurement_filter.as_mut() {
+                filter.seed(prev_measurement);
+            }
+            self.warmed_up = true;
+            self
+        }
+
+        /// Sets the setpoint. Prefer this over assigning `setpoint` directly.
+        pub fn set_setpoint(&mut self, setpoint: T) -> &mut Self {
+            self.setpoint = setpoint;
+            self
+        }
+
+        /// Restricts the setpoint to a physically meaningful range. Every step (and
+        /// `observe`) applies `mode` to the setpoint it uses, whether assigned through the
+        /// field, `set_setpoint` or passed to `step_with_setpoint`. The `setpoint` field
+        /// itself is left as assigned.
+        pub fn set_setpoint_limits(&mut self, limits: Limits<T>, mode: SetpointLimitMode) -> &mut Self {
+            self.setpoint_limits = Some((limits, mode));
+            self
+        }
+
+        pub fn clear_setpoint_limits(&mut self) -> &mut Self {
+            self.setpoint_limits = None;
+            self
+        }
+
+        pub fn setpoint_limits(&self) -> Option<(Limits<T>, SetpointLimitMode)> {
+            self.setpoint_limits
+        }
+
+        fn limit_setpoint(&self, setpoint: T) -> T {
+            match self.setpoint_limits {
+                Some((limits, SetpointLimitMode::Clamp)) => limits.clamp(setpoint),
+                None => setpoint,
+            }
+        }
+
+        /// Switches to manual mode: `step` returns `output` as `out` while the integrator
+        /// tracks `output - p`, so that `set_automatic` resumes without a bump.
+        pub fn set_manual(&mut self, output: T) -> &mut Self {
+            self.manual_output = Some(output);
+            self
+        }
+
+        /// Returns to automatic mode, continuing from the tracked integrator.
+        pub fn set_automatic(&mut self) -> &mut Self {
+            self.manual_output = None;
+            self
+        }
+
+        /// The operator output while in manual mode, `None` in automatic mode.
+        pub fn manual_output(&self) -> Option<T> {
+            self.manual_output
+        }
+
+        /// Passes every measurement through `filter` before it reaches the P, I and D terms.
+        pub fn set_measurement_filter(&mut self, filter: NotchFilter<T>) -> &mut Self {
+            self.measurement_filter = Some(filter);
+            self
+        }
+
+        pub fn clear_measurement_filter(&mut self) -> &mut Self {
+            self.measurement_filter = None;
+            self
+        }
+
+        pub fn step(&mut self, input: PidIn<T>) -> PidOut<T> {
+            self.step_detailed(input).base
+        }
+
+        /// Like `step`, also returning the pre-limit values and the error; see `PidOutDetailed`.
+        pub fn step_detailed(&mut self, input: PidIn<T>) -> PidOutDetailed<T> {
+            self.step_detailed_with_setpoint(input, self.setpoint)
+        }
+
+        /// Steps towards `setpoint` instead of `self.setpoint`, which is left unchanged, e.g.
+        /// for a reference trajectory computed per step. Does not count as a setpoint change
+        /// for `time_since_setpoint_change`.
+        pub fn step_with_setpoint(&mut self, input: PidIn<T>, setpoint: T) -> PidOut<T> {
+            self.step_detailed_with_setpoint(input, setpoint).base
+        }
+
+        fn step_detailed_with_setpoint(&mut self, input: PidIn<T>, setpoint: T) -> PidOutDetailed<T> {
+            let error_fn = self.error_fn;
+            self.step_with_error(input, setpoint, move |setpoint, measurement| error_with(error_fn, setpoint, measurement))
+        }
+
+        /// The output `step(input)` would return, running the full pipeline on a copy so no
+        /// state changes. To commit, call `step` with the same input; a preview that is not
+        /// committed leaves the next step's derivative and integral computed from the last
+        /// committed measurement, not the previewed one.
+        pub fn preview_step(&self, input: PidIn<T>) -> PidOut<T> {
+            let mut pid = *self;
+            pid.step(input)
+        }
+
+        /// The outputs `preview_step(input)` gives with `kp`, `ki` and `kd` in turn increased by
+        /// `delta`, for a finite-difference estimate of the output's sensitivity to each gain:
+        /// `(perturbed.out - preview_step(input).out) / delta`. No state changes.
+        pub fn gain_sensitivity(&self, input: PidIn<T>, delta: T) -> (PidOut<T>, PidOut<T>, PidOut<T>) {
+            let mut kp = *self;
+            kp.kp.scale = kp.kp.scale + delta;
+            let mut ki = *self;
+            ki.ki.scale = ki.ki.scale + delta;
+            let mut kd = *self;
+            kd.kd.scale = kd.kd.scale + delta;
+            (kp.preview_step(input), ki.preview_step(input), kd.preview_step(input))
+        }
+
+        /// Advances the integrator and derivative state with `measurement` like `step`, but
+        /// returns only the error, so the controller stays warm while the output comes from
+        /// elsewhere (e.g. a state estimator). Step count, statistics and last output are
+        /// not updated.
+        pub fn observe(&mut self, measurement: T, tdelta: T) -> T {
+            let tdelta = PidIn::new(measurement, tdelta).effective_tdelta();
+            let measurement = match self.measurement_filter.as_mut() {
+                Some(filter) => filter.filter(measurement, tdelta),
+                None => measurement,
+            };
+            let setpoint = self.limit_setpoint(self.setpoint);
+            let offset = error_with(self.error_fn, setpoint, measurement);
+            self.ki.step(offset, tdelta);
+            self.kd.step(measurement, tdelta);
+            offset
+        }
+
+        fn step_with_error<E: FnMut(T, T) -> T>(
+            &mut self, input: PidIn<T>, setpoint: T, mut error: E,
+        ) -> PidOutDetailed<T> {
+            let tdelta = input.effective_tdelta();
+            if self.setpoint != self.last_setpoint {
+                self.last_setpoint = self.setpoint;
+                self.time_at_current_setpoint = T::zero();
+            }
+            self.time_at_current_setpoint = self.time_at_current_setpoint + tdelta;
+            let setpoint = self.limit_setpoint(setpoint);
+            let measurement = match self.measurement_filter.as_mut() {
+                Some(filter) => filter.filter(input.measurement, tdelta),
+                None => input.measurement,
+            };
+            let offset = error(setpoint, measurement);
+            let p_raw = self.kp.step_raw(error(self.kp.setpoint_weight * setpoint, measurement));
+            let (p, kp_saturated) = self.kp.limits.clamp_with_flag(p_raw);
+            let i_raw = match self.manual_output {
+                Some(manual) => {
+                    self.ki.set_accumulate(self.ki.limits.clamp(manual - p));
+                    self.ki.integrated_time = self.ki.integrated_time + tdelta;
+                    manual - p
+                }
+                None => {
+                    if offset.abs() > self.integral_reset_threshold {
+                        self.ki.set_accumulate(T::zero());
+                    }
+                    self.ki.step_raw(offset, tdelta, self.tracking_gain * self.saturation_error)
+                }
+            };
+            let (i, ki_saturated) = self.ki.limits.clamp_with_flag(i_raw);
+            let d_raw = self.kd.step_raw(measurement, tdelta);
+            let (d, kd_saturated) = self.kd.limits.clamp_with_flag(d_raw);
+            self.step_count = self.step_count.wrapping_add(1);
+            self.error_stats.update(offset);
+            self.iae = self.iae + offset.abs() * tdelta;
+            self.ise = self.ise + offset * offset * tdelta;
+            if let Some((tolerance, _)) = self.convergence_criteria {
+                self.time_within_tolerance = if offset.abs() <= tolerance {
+                    self.time_within_tolerance + tdelta
+                } else {
+                    T::zero()
+                };
+            }
+            let (clamped, output_saturated) = self.limits.clamp_with_flag(p + i + d);
+            self.saturation_error = match self.manual_output {
+                Some(_) => T::zero(),
+                None => clamped - (p + i + d),
+            };
+            if self.manual_output.is_none() {
+                self.ki.apply_back_calc_correction(self.saturation_error, i - i_raw);
+            }
+            let out = self.manual_output.unwrap_or_else(|| self.quantize(clamped));
+            self.last_out = PidOut::new(p, i, d, out);
+            self.last_out.saturation = SaturationFlags{
+                kp_saturated, ki_saturated, kd_saturated,
+                output_saturated: output_saturated && self.manual_output.is_none(),
+            };
+            PidOutDetailed{base: self.last_out, p_raw, i_raw, d_raw, output_raw: p + i + d, error: offset}
+        }
+
+        /// Enables back-calculation anti-windup: each step feeds
+        /// `tracking_gain * (saturated_out - unsaturated_out)` of the previous step back into
+        /// the integrator (see `KITerm::step_with_back_calc`). Zero, the default, disables it.
+        pub fn set_tracking_gain(&mut self, gain: T) -> Result<&mut Self, PidError> {
+            if !gain.is_finite() || gain < T::zero() {
+                return Err(PidError::InvalidValue);
+            }
+            self.tracking_gain = gain;
+            Ok(self)
+        }
+
+        /// Zeroes the integrator before any step whose `|error|` exceeds `threshold`, so a
+        /// large disturbance starts from a clean integral. Infinite, the default, never resets.
+        pub fn set_integral_reset_threshold(&mut self, threshold: T) -> Result<&mut Self, PidError> {
+            if threshold.is_nan() || threshold < T::zero() {
+                return Err(PidError::InvalidValue);
+            }
+            self.integral_reset_threshold = threshold;
+            Ok(self)
+        }
+
+        /// Rounds the clamped output to the nearest multiple of `step`, e.g. `1.0` for integer
+        /// PWM duty cycles. The output limits still apply after rounding. Must be positive;
+        /// use `clear_quantization` to disable.
+        pub fn set_quantization_step(&mut self, step: T) -> Result<&mut Self, PidError> {
+            if !step.is_finite() || step <= T::zero() {
+                return Err(PidError::InvalidValue);
+            }
+            self.quantization_step = step;
+            Ok(self)
+        }
+
+        pub fn clear_quantization(&mut self) -> &mut Self {
+            self.quantization_step = T::zero();
+            self
+        }
+
+        fn quantize(&self, out: T) -> T {
+            if self.quantization_step == T::zero() {
+                return out;
+            }
+            self.limits.clamp((out / self.quantization_step).round() * self.quantization_step)
+        }
+
+        /// The output of the most recent `step`, or `PidOut::default()` before the first
+        /// step and after `reset`.
+        pub fn last_output(&self) -> PidOut<T> {
+            self.last_out
+        }
+
+        /// Replaces `setpoint - measurement` with `f(setpoint, measurement)` as the error fed
+        /// to the terms. A plain function pointer keeps `PidCtrl` `Copy` and `no_std`;
+        /// non-capturing closures coerce to it. Not serialized.
+        pub fn set_error_fn(&mut self, f: fn(T, T) -> T) -> &mut Self {
+            self.error_fn = Some(ErrorFn(f));
+            self
+        }
+
+        /// Copies gains, limits and all other configuration to `other`, leaving its state and
+        /// setpoint untouched.
+        pub fn copy_gains_to(&self, other: &mut PidCtrl<T>) {
+            other.kp = self.kp;
+            let ki = other.ki;
+            other.ki = self.ki;
+            other.ki.copy_state_from(&ki);
+            let kd = other.kd;
+            other.kd = self.kd;
+            other.kd.copy_state_from(&kd);
+            other.limits = self.limits;
+            other.error_fn = self.error_fn;
+            other.quantization_step = self.quantization_step;
+            other.tracking_gain = self.tracking_gain;
+            other.integral_reset_threshold = self.integral_reset_threshold;
+            other.convergence_criteria = self.convergence_criteria;
+            other.sample_period = self.sample_period;
+            other.setpoint_limits = self.setpoint_limits;
+            // a filter new to `other` starts from its last measurement
+            other.measurement_filter = self.measurement_filter.map(|mut filter| {
+                match other.measurement_filter {
+                    Some(state) => filter.copy_state_from(&state),
+                    None => {
+                        filter.seed(other.kd.prev_measurement());
+                    }
+                }
+                filter
+            });
+        }
+
+        /// Copies the integrator, derivative, filter and back-calculation state, step count,
+        /// last output and convergence timer to `other`, leaving its gains, limits and setpoint
+        /// untouched. Filter state is only copied when both controllers have a measurement filter.
+        pub fn copy_state_to(&self, other: &mut PidCtrl<T>) {
+            other.ki.copy_state_from(&self.ki);
+            other.kd.copy_state_from(&self.kd);
+            if let (Some(filter), Some(state)) = (other.measurement_filter.as_mut(), &self.measurement_filter) {
+                filter.copy_state_from(state);
+            }
+            other.step_count = self.step_count;
+            other.last_out = self.last_out;
+            other.saturation_error = self.saturation_error;
+            other.time_within_tolerance = self.time_within_tolerance;
+        }
+
+        /// Returns the controller to its freshly configured state.
+        ///
+        /// Cleared:
+        /// - integrator, derivative and measurement filter state
+        /// - step count, error statistics and the IAE and ISE integrals
+        /// - last output and back-calculation error
+        /// - setpoint and convergence timers, and the warm-start flag
+        ///
+        /// Kept: gains, limits, the setpoint and all other configuration.
+        pub fn reset(&mut self) -> &mut Self {
+            self.ki.set_accumulate(T::zero());
+            self.ki.integrated_time = T::zero();
+            self.kd.seed(T::zero());
+            self.kd.initial_derivative = None;
+            if let Some(filter) = self.measurement_filter.as_mut() {
+                filter.seed(T::zero());
+            }
+            self.step_count = 0;
+            self.error_stats = ErrorStats::default();
+            self.iae = T::zero();
+            self.ise = T::zero();
+            self.warmed_up = false;
+            self.last_out = PidOut::default();
+            self.saturation_error = T::zero();
+            self.time_at_current_setpoint = T::zero();
+            self.time_within_tolerance = T::zero();
+            self
+        }
+
+        /// Clears only the derivative state, seeding its history with `measurement` (the next
+        /// expected measurement) so the following step produces no derivative spike, e.g.
+        /// after a measurement outage. The integrator is kept.
+        pub fn reset_derivative(&mut self, measurement: T) -> &mut Self {
+            self.kd.seed(measurement);
+            self.kd.initial_derivative = None;
+            self
+        }
+
+        /// Clears only the integrator and its integrated time, keeping the derivative state.
+        pub fn reset_integral(&mut self) -> &mut Self {
+            self.ki.set_accumulate(T::zero());
+            self.ki.integrated_time = T::zero();
+            self
+        }
+
+        /// Takes over the state of `other` (see `copy_state_to`) while keeping this
+        /// controller's gains and setpoint, for bumpless handover from a hot standby.
+        pub fn warm_start_from(&mut self, other: &PidCtrl<T>) -> &mut Self {
+            other.copy_state_to(self);
+            self.warmed_up = true;
+            self
+        }
+
+        /// `true` once `init` or `warm_start_from` has been called, until the next `reset`.
+        pub fn is_warmed_up(&self) -> bool {
+            self.warmed_up
+        }
+
+        /// Sets symmetric `±val` limits (see `Limits::set_limit`) on the output and on each
+        /// of the P, I and D terms.
+        pub fn set_all_limits(&mut self, output: T, p: T, i: T, d: T) -> &mut Self {
+            self.limits.set_limit(output);
+            self.kp.limits.set_limit(p);
+            self.ki.limits.set_limit(i);
+            self.kd.limits.set_limit(d);
+            self
+        }
+
+        /// The range of `out` reachable with the current term limits: the sum of the P, I
+        /// and D bounds, passed through the output limits. An output limit outside this
+        /// range can never be reached.
+        pub fn effective_output_range(&self) -> Limits<T> {
+            let terms = [self.kp.limits, self.ki.limits, self.kd.limits];
+            let lower = terms.iter().fold(T::zero(), |sum, limits| sum + limits.lower);
+            let upper = terms.iter().fold(T::zero(), |sum, limits| sum + limits.upper);
+            Limits{lower: self.limits.clamp(lower), upper: self.limits.clamp(upper)}
+        }
+
+        /// Restores the default unbounded limits on the output and on every term.
+        pub fn clear_all_limits(&mut self) -> &mut Self {
+            self.limits = Limits::new();
+            self.kp.limits = Limits::new();
+            self.ki.limits = Limits::new();
+            self.kd.limits = Limits::new();
+            self
+        }
+
+        /// Statistics of `setpoint - measurement` (or the custom error function) over every
+        /// step since construction or the last `reset`.
+        pub fn error_stats(&self) -> &ErrorStats<T> {
+            &self.error_stats
+        }
+
+        /// Integrated absolute error `∫|e| dt` over every step since construction or the last
+        /// `reset` or `reset_iae`, for comparing tunings.
+        pub fn iae(&self) -> T {
+            self.iae
+        }
+
+        pub fn reset_iae(&mut self) -> &mut Self {
+            self.iae = T::zero();
+            self
+        }
+
+        /// Integrated squared error `∫e² dt`, like `iae` but weighting large errors more.
+        pub fn ise(&self) -> T {
+            self.ise
+        }
+
+        pub fn reset_ise(&mut self) -> &mut Self {
+            self.ise = T::zero();
+            self
+        }
+
+        /// Sum of the time deltas stepped since `setpoint` last changed, including the step
+        /// that saw the change. Cleared by `reset`.
+        pub fn time_since_setpoint_change(&self) -> T {
+            self.time_at_current_setpoint
+        }
+
+        /// Makes `is_converged` report whether `|error| <= tolerance` has held for at least
+        /// `min_duration` of consecutive steps.
+        pub fn set_convergence_criteria(&mut self, tolerance: T, min_duration: T) -> &mut Self {
+            self.convergence_criteria = Some((tolerance, min_duration));
+            self.time_within_tolerance = T::zero();
+            self
+        }
+
+        /// Always `false` until `set_convergence_criteria` is called.
+        pub fn is_converged(&self) -> bool {
+            match self.convergence_criteria {
+                Some((_, min_duration)) => self.time_within_tolerance >= min_duration,
+                None => false,
+            }
+        }
+
+        /// Number of calls to `step` since construction or the last `reset`. Wraps around
+        /// to zero on overflow.
+        pub fn step_count(&self) -> u64 {
+            self.step_count
+        }
+
+        /// Sets the setpoint from `schedule` at `current_time` and steps. An empty schedule
+        /// leaves the setpoint unchanged.
+        pub fn step_schedule<const N: usize>(
+            &mut self, schedule: &SetpointSchedule<T, N>, current_time: T, measurement: T, tdelta: T,
+        ) -> PidOut<T> {
+            if let Some(setpoint) = schedule.setpoint_at(current_time) {
+                self.set_setpoint(setpoint);
+            }
+            self.step(PidIn::new(measurement, tdelta))
+        }
+
+        /// Lazily steps through `inputs`: each output is computed when the returned iterator
+        /// is advanced, so no allocation is needed.
+        pub fn step_all<I: Iterator<Item = PidIn<T>>>(&mut self, inputs: I) -> StepAll<'_, T, I> {
+            StepAll{pid: self, inputs}
+        }
+
+        /// Runs `step_schedule` over `measurements` sampled every `tdelta`, starting at time zero.
+        #[cfg(feature = "std")]
+        pub fn run_schedule<const N: usize>(
+            &mut self, schedule: &SetpointSchedule<T, N>, measurements: &[T], tdelta: T,
+        ) -> std::vec::Vec<PidOut<T>> {
+            let mut time = T::zero();
+            measurements.iter().map(|&measurement| {
+                let out = self.step_schedule(schedule, time, measurement, tdelta);
+                time = time + tdelta;
+                out
+            }).collect()
+        }
+
+        pub fn term(&self, which: TermKind) -> &dyn PidTerm<T> {
+            match which {
+                TermKind::P => &self.kp,
+                TermKind::I => &self.ki,
+                TermKind::D => &self.kd,
+            }
+        }
+
+        pub fn term_mut(&mut self, which: TermKind) -> &mut dyn PidTerm<T> {
+            match which {
+                TermKind::P => &mut self.kp,
+                TermKind::I => &mut self.ki,
+                TermKind::D => &mut self.kd,
+            }
+        }
+
+        /// Steps with `limits` in place of `self.limits` as the output limits, for this step
+        /// only; `self.limits` is left as it was. The caller keeps `limits` consistent.
+        pub fn step_with_limits(&mut self, input: PidIn<T>, limits: &Limits<T>) -> PidOut<T> {
+            let stored = core::mem::replace(&mut self.limits, *limits);
+            let out = self.step(input);
+            self.limits = stored;
+            out
+        }
+
+        /// Steps only if the measurement moved by at least `threshold` from
+        /// `kd.prev_measurement()`; otherwise returns `None` and leaves all state untouched,
+        /// so the skipped time is not integrated.
+        pub fn step_on_change(&mut self, input: PidIn<T>, threshold: T) -> Option<PidOut<T>> {
+            if (input.measurement - self.kd.prev_measurement()).abs() < threshold {
+                return None;
+            }
+            Some(self.step(input))
+        }
+
+        /// Like `step`, also returning the clamped time delta that was used
+        /// (see `PidIn::effective_tdelta`).
+        pub fn step_with_timing(&mut self, input: PidIn<T>) -> (PidOut<T>, T) {
+            (self.step(input), input.effective_tdelta())
+        }
+
+        /// Stores the period `step_fixed` uses, for loops running at a fixed rate. Must be
+        /// positive and finite.
+        pub fn set_sample_period(&mut self, dt: T) -> Result<&mut Self, PidError> {
+            if !dt.is_finite() || dt <= T::zero() {
+                return Err(PidError::InvalidValue);
+            }
+            self.sample_period = Some(dt);
+            Ok(self)
+        }
+
+        /// The period set by `set_sample_period`, if any.
+        pub fn sample_period(&self) -> Option<T> {
+            self.sample_period
+        }
+
+        /// `step` with the period set by `set_sample_period`. Panics in debug builds if no
+        /// period was set; release builds fall back to `T::epsilon()`, the smallest `tdelta`
+        /// `PidIn` allows.
+        pub fn step_fixed(&mut self, measurement: T) -> PidOut<T> {
+            debug_assert!(
+                self.sample_period.is_some(),
+                "PidCtrl::step_fixed: call set_sample_period first",
+            );
+            let tdelta = self.sample_period.unwrap_or_else(T::epsilon);
+            self.step(PidIn::new(measurement, tdelta))
+        }
+
+        /// Like `step`, but rejects a non-finite measurement or a non-positive time delta
+        /// with `PidError::InvalidValue` before touching any state. A non-finite output is
+        /// reported the same way, after the step has run.
+        pub fn try_step(&mut self, input: PidIn<T>) -> Result<PidOut<T>, PidError> {
+            if !input.measurement.is_finite() || input.tdelta <= T::zero() || input.tdelta.is_nan() {
+                return Err(PidError::InvalidValue);
+            }
+            let out = self.step(input);
+            if !out.out.is_finite() {
+                return Err(PidError::InvalidValue);
+            }
+            Ok(out)
+        }
+    }
+
+impl<T: FloatCore + core::default::Default + core::fmt::Display> PidCtrl<T> {
+    /// Writes the configuration on one line, e.g. `PID kp=2 ki=0.5 kd=0 sp=1 out_lim=[-5, 10]`,
+    /// without any runtime state. The output limits are formatted like `Limits`' `Display`
+    /// and left out when unbounded (see `Limits::is_default`).
+    pub fn write_config_summary<W: core::fmt::Write>(&self, w: &mut W) -> core::fmt::Result {
+        write!(w, "PID kp={} ki={} kd={} sp={}", self.kp.scale, self.ki.scale, self.kd.scale, self.setpoint)?;
+        if !self.limits.is_default() {
+            write!(w, " out_lim={}", self.limits)?;
+        }
+        Ok(())
+    }
+
+    /// `write_config_summary` into a `String`.
+    #[cfg(feature = "std")]
+    pub fn config_summary(&self) -> std::string::String {
+        let mut summary = std::string::String::new();
+        self.write_config_summary(&mut summary).expect("writing to a String cannot fail");
+        summary
+    }
+}
+
+/// A serialized `PidCtrl` tagged with the layout version it was written with, so that
+/// configurations stored across firmware upgrades are rejected rather than misread.
+#[cfg(feature = "serde")]
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct PidCtrlConfig<T: FloatCore + core::default::Default> {
+    pub version: u32,
+    pub pid: PidCtrl<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: FloatCore + core::default::Default> PidCtrl<T> {
+    pub fn to_config(&self) -> PidCtrlConfig<T> {
+        PidCtrlConfig{version: Self::VERSION, pid: *self}
+    }
+
+    /// Fails with `PidError::VersionMismatch` unless `cfg.version` is `PidCtrl::VERSION`.
+    pub fn from_config(cfg: PidCtrlConfig<T>) -> Result<PidCtrl<T>, PidError> {
+        if cfg.version != Self::VERSION {
+            return Err(PidError::VersionMismatch);
+        }
+        Ok(cfg.pid)
+    }
+}
+
+/// Runs `baseline` and `candidate` side by side over `measurements` sampled every `tdelta`
+/// and returns both output sequences, for A/B testing gains in simulation. `candidate`
+/// first takes over the integrator and derivative state of `baseline` (see
+/// `PidCtrl::copy_state_to`), so the outputs differ only by configuration.
+#[cfg(feature = "std")]
+pub fn compare_gains<T: FloatCore + core::default::Default>(
+    mut baseline: PidCtrl<T>, mut candidate: PidCtrl<T>, measurements: &[T], tdelta: T,
+) -> (std::vec::Vec<PidOut<T>>, std::vec::Vec<PidOut<T>>) {
+    baseline.copy_state_to(&mut candidate);
+    measurements.iter().map(|&measurement| {
+        let input = PidIn::new(measurement, tdelta);
+        (baseline.step(input), candidate.step(input))
+    }).unzip()
+}
+
+// Flat little-endian IEEE 754 layout for NVM storage, `T` at a time:
+// kp, ki, kd, then lower and upper of kp.limits, ki.limits, kd.limits and limits.
+macro_rules! impl_nvm_bytes {
+    ($float:ty, $len:expr) => {
+        impl PidCtrl<$float> {
+            /// Size of the `to_bytes` image: three gains and four pairs of limits, i.e. 44 bytes
+            /// for `f32` and 88 for `f64`.
+            pub const NVM_LEN: usize = $len;
+
+            /// Packs the gains and limits as 11 little-endian IEEE 754 values, value `k` at
+            /// bytes `k * size_of::<T>()..(k + 1) * size_of::<T>()`:
+            ///
+            /// | k | value | k | value | k | value |
+            /// |---|-------|---|-------|---|-------|
+            /// | 0 | `kp` | 3 | `kp.limits` lower | 7 | `kd.limits` lower |
+            /// | 1 | `ki` | 4 | `kp.limits` upper | 8 | `kd.limits` upper |
+            /// | 2 | `kd` | 5 | `ki.limits` lower | 9 | `limits` lower |
+            /// |   |      | 6 | `ki.limits` upper | 10 | `limits` upper |
+            ///
+            /// No other configuration or state is stored. The image is a fixed `[u8; NVM_LEN]`
+            /// per float type rather than a caller-chosen `[u8; N]`, so a wrongly sized buffer
+            /// fails to compile instead of at runtime, and it holds the output limits as well
+            /// as the term limits, since both are part of a tuning.
+            pub fn to_bytes(&self) -> [u8; $len] {
+                let values = [
+                    self.kp.scale, self.ki.scale, self.kd.scale,
+                    self.kp.limits.lower, self.kp.limits.upper, self.ki.limits.lower, self.ki.limits.upper,
+                    self.kd.limits.lower, self.kd.limits.upper, self.limits.lower, self.limits.upper,
+                ];
+                let mut bytes = [0; $len];
+                for (chunk, value) in bytes.chunks_exact_mut(core::mem::size_of::<$float>()).zip(values) {
+                    chunk.copy_from_slice(&value.to_le_bytes());
+                }
+                bytes
+            }
+
+            /// Unpacks `to_bytes` into an otherwise default controller. `None` if a gain is
+            /// not finite or a pair of limits is invalid (NaN or `lower > upper`), e.g. for
+            /// erased or corrupted storage.
+            pub fn from_bytes(bytes: &[u8; $len]) -> Option<PidCtrl<$float>> {
+                let mut values = [0.0; $len / core::mem::size_of::<$float>()];
+                for (value, chunk) in values.iter_mut().zip(bytes.chunks_exact(core::mem::size_of::<$float>())) {
+                    *value = <$float>::from_le_bytes(chunk.try_into().unwrap());
+                }
+                let [kp, ki, kd, limits @ ..] = values;
+                if !(kp.is_finite() && ki.is_finite() && kd.is_finite()) {
+                    return None;
+                }
+                let mut pid = PidCtrl::new_with_pid(kp, ki, kd);
+                pid.kp.limits = Limits::try_new(limits[0], limits[1]).ok()?;
+                pid.ki.limits = Limits::try_new(limits[2], limits[3]).ok()?;
+                pid.kd.limits = Limits::try_new(limits[4], limits[5]).ok()?;
+                pid.limits = Limits::try_new(limits[6], limits[7]).ok()?;
+                Some(pid)
+            }
+        }
+    };
+}
+
+impl_nvm_bytes!(f32, 44);
+impl_nvm_bytes!(f64, 88);
+
+/// A sequence of `(setpoint, duration)` segments, each held for its duration in turn.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct SetpointSchedule<T: FloatCore + core::default::Default, const N: usize> {
+    segments: [(T, T); N],
+}
+
+impl<T: FloatCore + core::default::Default, const N: usize> SetpointSchedule<T, N> {
+    pub fn new(segments: [(T, T); N]) -> Self {
+        SetpointSchedule{segments}
+    }
+
+    /// Setpoint of the segment covering `time`, measured from the start of the schedule.
+    /// Segments cover `[start, start + duration)`; past the end the last setpoint is held.
+    /// Returns `None` only for an empty schedule.
+    pub fn setpoint_at(&self, time: T) -> Option<T> {
+        let mut end = T::zero();
+        for &(setpoint, duration) in self.segments.iter() {
+            end = end + duration;
+            if time < end {
+                return Some(setpoint);
+            }
+        }
+        self.segments.last().map(|&(setpoint, _)| setpoint)
+    }
+
+    pub fn total_duration(&self) -> T {
+        self.segments.iter().fold(T::zero(), |total, &(_, duration)| total + duration)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct PidIn <T: FloatCore + core::default::Default> {
+    measurement: T,
+    tdelta: T,
+    // absent from `PidIn`s serialized before it was added
+    #[cfg_attr(feature = "serde", serde(default))]
+    uncertainty: T,
+}
+
+impl<T: FloatCore + core::default::Default> PidIn<T> {
+        pub fn new(measurement:T, tdelta:T) -> Self {
+            Self::new_with_uncertainty(measurement, tdelta, T::zero())
+        }
+
+        /// Also records the measurement noise variance. Reserved for noise-aware controllers:
+        /// `PidCtrl::step` currently ignores it, but it is serialized for logging.
+        pub fn new_with_uncertainty(measurement: T, tdelta: T, uncertainty: T) -> Self {
+            PidIn{measurement, tdelta, uncertainty}
+        }
+
+        pub fn uncertainty(&self) -> T {
+            self.uncertainty
+        }
+
+        /// The time delta `PidCtrl::step` uses: the given `tdelta` clamped to
+        /// `[T::epsilon(), T::infinity()]`. The raw value is kept so `try_step` can reject it.
+        pub fn effective_tdelta(&self) -> T {
+            self.tdelta.min(T::infinity()).max(T::epsilon())
+        }
+    }
+
+#[cfg(feature = "std")]
+impl PidIn<f64> {
+    /// Takes `tdelta` in seconds from `duration`.
+    pub fn from_duration(measurement: f64, duration: std::time::Duration) -> Self {
+        Self::new(measurement, duration.as_secs_f64())
+    }
+
+    /// Takes `tdelta` as the time from `last` to `now` and advances `last` to `now`, for
+    /// loops timed by the wall clock. A `now` earlier than `last` gives a zero `tdelta`.
+    pub fn from_instant(measurement: f64, now: std::time::Instant, last: &mut std::time::Instant) -> Self {
+        let elapsed = now.saturating_duration_since(*last);
+        *last = now;
+        Self::from_duration(measurement, elapsed)
+    }
+}
+
+/// Which limits clamped their value during a `PidCtrl::step`.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct SaturationFlags {
+    pub kp_saturated: bool,
+    pub ki_saturated: bool,
+    pub kd_saturated: bool,
+    /// Never set in manual mode.
+    pub output_saturated: bool,
+}
+
+impl SaturationFlags {
+    pub fn any(&self) -> bool {
+        self.kp_saturated || self.ki_saturated || self.kd_saturated || self.output_saturated
+    }
+}
+
+/// The result of one `PidCtrl::step`, in the units of the output. Fields are in
+/// `p, i, d, out` order, matching `PidOut::new`.
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct PidOut <T: FloatCore + core::default::Default> {
+    /// Proportional contribution, after the P term limits.
+    pub p: T,
+    /// Integral contribution, i.e. the running accumulator after the I term limits.
+    pub i: T,
+    /// Derivative contribution, after the D term limits.
+    pub d: T,
+    /// Controller output: `p + i + d` clamped to the output limits and, if configured,
+    /// quantized. In manual mode it is the manual output instead.
+    pub out: T,
+    /// Set by `PidCtrl::step`; all clear for outputs built with `PidOut::new`. Diagnostic
+    /// only: comparisons and hashing ignore it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub saturation: SaturationFlags,
+}
+
+impl<T: FloatCore + core::default::Default> PartialEq for PidOut<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_array() == other.to_array()
+    }
+}
+
+impl<T: FloatCore + core::default::Default> PartialOrd for PidOut<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        self.to_array().partial_cmp(&other.to_array())
+    }
+}
+
+impl<T: FloatCore + core::default::Default + core::hash::Hash> core::hash::Hash for PidOut<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.to_array().hash(state);
+    }
+}
+
+impl<T: FloatCore + core::default::Default> PidOut<T> {
+        /// Takes the fields in declaration order. The relation `out == clamp(p + i + d)` is
+        /// not checked, so callers can build arbitrary outputs, e.g. for tests.
+        pub fn new(p:T, i:T, d:T, out:T) -> Self {
+            Self{p, i, d, out, saturation: SaturationFlags::default()}
+        }
+
+        /// `p / |out|`, or zero when `out` is zero. Signed, so terms pulling against the
+        /// output have a negative fraction.
+        pub fn p_fraction(&self) -> T {
+            analysis::contribution_fraction(self.p, self.out.abs())
+        }
+
+        /// `i / |out|`, see `p_fraction`.
+        pub fn i_fraction(&self) -> T {
+            analysis::contribution_fraction(self.i, self.out.abs())
+        }
+
+        /// `d / |out|`, see `p_fraction`.
+        pub fn d_fraction(&self) -> T {
+            analysis::contribution_fraction(self.d, self.out.abs())
+        }
+
+        /// Each field replaced by its absolute value.
+        pub fn abs_components(&self) -> PidOut<T> {
+            PidOut::new(self.p.abs(), self.i.abs(), self.d.abs(), self.out.abs())
+        }
+
+        /// Each field replaced by its `FloatCore::signum`, so `±0.0` maps to `±1.0`.
+        pub fn signum_components(&self) -> PidOut<T> {
+            PidOut::new(self.p.signum(), self.i.signum(), self.d.signum(), self.out.signum())
+        }
+
+        /// Compares all four fields within `eps`: `|self.x - other.x| <= eps`. Equal
+        /// infinities compare equal.
+        pub fn approx_eq(&self, other: &PidOut<T>, eps: T) -> bool {
+            let close = |a: T, b: T| a == b || (a - b).abs() <= eps;
+            close(self.p, other.p) && close(self.i, other.i) && close(self.d, other.d) && close(self.out, other.out)
+        }
+
+        /// Component-wise `self * (1 - t) + other * t`, with `t` clamped to `[0, 1]`.
+        pub fn lerp(&self, other: &PidOut<T>, t: T) -> PidOut<T> {
+            let t = t.min(T::one()).max(T::zero());
+            let mix = |a: T, b: T| a * (T::one() - t) + b * t;
+            PidOut::new(mix(self.p, other.p), mix(self.i, other.i), mix(self.d, other.d), mix(self.out, other.out))
+        }
+
+        /// Returns `[p, i, d, out]`.
+        pub fn to_array(&self) -> [T; 4] {
+            [self.p, self.i, self.d, self.out]
+        }
+
+        /// Builds from `[p, i, d, out]`, the same order as `new`.
+        pub fn from_array(arr: [T; 4]) -> Self {
+            let [p, i, d, out] = arr;
+            Self::new(p, i, d, out)
+        }
+
+        /// Returns `(p, i, d, out)`.
+        pub fn to_tuple(&self) -> (T, T, T, T) {
+            (self.p, self.i, self.d, self.out)
+        }
+
+        /// Builds from `(p, i, d, out)`, the same order as `new`.
+        pub fn from_tuple(t: (T, T, T, T)) -> Self {
+            let (p, i, d, out) = t;
+            Self::new(p, i, d, out)
+        }
+
+        /// Returns the component with the largest magnitude and which term it came from.
+        /// Ties go to the earlier term in `p`, `i`, `d` order.
+        pub fn dominant_term(&self) -> (T, TermKind) {
+            let mut dominant = (self.p, TermKind::P);
+            if self.i.abs() > dominant.0.abs() {
+                dominant = (self.i, TermKind::I);
+            }
+            if self.d.abs() > dominant.0.abs() {
+                dominant = (self.d, TermKind::D);
+            }
+            dominant
+        }
+
+        /// Largest of `p`, `i` and `d` (signed).
+        pub fn component_max(&self) -> T {
+            self.p.max(self.i).max(self.d)
+        }
+
+        /// Smallest of `p`, `i` and `d` (signed).
+        pub fn component_min(&self) -> T {
+            self.p.min(self.i).min(self.d)
+        }
+    }
+
+impl<T: FloatCore + core::default::Default + core::fmt::Display> PidOut<T> {
+    /// Writes one `timestamp,p,i,d,out` line, newline included, for logging without `std`.
+    pub fn write_csv_row<W: core::fmt::Write>(&self, w: &mut W, timestamp: T) -> core::fmt::Result {
+        writeln!(w, "{},{},{},{},{}", timestamp, self.p, self.i, self.d, self.out)
+    }
+
+    /// One `timestamp,p,i,d,out` line, newline included; see `CsvLogger` for a whole log.
+    #[cfg(feature = "std")]
+    pub fn to_csv_row(&self, timestamp: f64) -> std::string::String {
+        std::format!("{},{},{},{},{}\n", timestamp, self.p, self.i, self.d, self.out)
+    }
+}
+
+/// Collects timestamped outputs and formats them as CSV with a `timestamp,p,i,d,out` header.
+#[cfg(feature = "std")]
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct CsvLogger<T: FloatCore + core::default::Default> {
+    rows: std::vec::Vec<(f64, PidOut<T>)>,
+}
+
+#[cfg(feature = "std")]
+impl<T: FloatCore + core::default::Default + core::fmt::Display> CsvLogger<T> {
+    pub fn new() -> Self {
+        CsvLogger{rows: std::vec::Vec::new()}
+    }
+
+    pub fn push(&mut self, timestamp: f64, out: PidOut<T>) {
+        self.rows.push((timestamp, out));
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The header line followed by one `PidOut::to_csv_row` line per entry, oldest first.
+    pub fn to_csv(&self) -> std::string::String {
+        let mut csv = std::string::String::from("timestamp,p,i,d,out\n");
+        for (timestamp, out) in &self.rows {
+            csv.push_str(&out.to_csv_row(*timestamp));
+        }
+        csv
+    }
+}
+
+/// A `PidOut` together with the intermediate values `PidCtrl::step_detailed` computed it from,
+/// for debugging and tuning.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct PidOutDetailed<T: FloatCore + core::default::Default> {
+    /// What `step` returns, saturation flags included.
+    pub base: PidOut<T>,
+    /// Proportional contribution before the P term limits.
+    pub p_raw: T,
+    /// Integrator before the I term limits; see `KITerm::raw_accumulate`. In manual mode,
+    /// the value the integrator tracks, `manual_output - p`.
+    pub i_raw: T,
+    /// Derivative contribution before the D term limits.
+    pub d_raw: T,
+    /// `p + i + d` of the limited terms, before the output limits and quantization.
+    pub output_raw: T,
+    /// The error the terms acted on: `setpoint - measurement` unless an error function is
+    /// set, after any measurement filter.
+    pub error: T,
+}
+
+/// Fixed-capacity ring buffer of the last `N` outputs, oldest first.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PidHistory<T: FloatCore + core::default::Default, const N: usize> {
+    buffer: [PidOut<T>; N],
+    len: usize,
+    next: usize,
+}
+
+impl<T: FloatCore + core::default::Default, const N: usize> PidHistory<T, N> {
+    pub fn new() -> Self {
+        PidHistory{buffer: [PidOut::default(); N], len: 0, next: 0}
+    }
+
+    /// Appends `out`, overwriting the oldest entry once full.
+    pub fn push(&mut self, out: PidOut<T>) {
+        if N == 0 {
+            return;
+        }
+        self.buffer[self.next] = out;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PidOut<T>> {
+        let start = if N == 0 { 0 } else { (self.next + N - self.len) % N };
+        (0..self.len).map(move |k| &self.buffer[(start + k) % N])
+    }
+
+    /// Mean of `out` over the stored entries; NaN when empty.
+    pub fn mean_out(&self) -> T {
+        let sum = self.iter().fold(T::zero(), |sum, out| sum + out.out);
+        sum / T::from(self.len).unwrap()
+    }
+
+    /// Largest `out` stored; negative infinity when empty.
+    pub fn max_out(&self) -> T {
+        self.iter().fold(T::neg_infinity(), |max, out| max.max(out.out))
+    }
+
+    /// Smallest `out` stored; infinity when empty.
+    pub fn min_out(&self) -> T {
+        self.iter().fold(T::infinity(), |min, out| min.min(out.out))
+    }
+}
+
+impl<T: FloatCore + core::default::Default, const N: usize> Default for PidHistory<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A `PidCtrl` that records every output of `step` in a `PidHistory`.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct PidCtrlWithHistory<T: FloatCore + core::default::Default, const N: usize> {
+    pub pid: PidCtrl<T>,
+    pub history: PidHistory<T, N>,
+}
+
+impl<T: FloatCore + core::default::Default, const N: usize> PidCtrlWithHistory<T, N> {
+    pub fn new(pid: PidCtrl<T>) -> Self {
+        PidCtrlWithHistory{pid, history: PidHistory::new()}
+    }
+
+    pub fn step(&mut self, input: PidIn<T>) -> PidOut<T> {
+        let out = self.pid.step(input);
+        self.history.push(out);
+        out
+    }
+}
+
+/// Iterator returned by `PidCtrl::step_all`.
+#[derive(Debug)]
+pub struct StepAll<'a, T: FloatCore + core::default::Default, I> {
+    pid: &'a mut PidCtrl<T>,
+    inputs: I,
+}
+
+impl<T: FloatCore + core::default::Default, I: Iterator<Item = PidIn<T>>> Iterator for StepAll<'_, T, I> {
+    type Item = PidOut<T>;
+
+    fn next(&mut self) -> Option<PidOut<T>> {
+        self.inputs.next().map(|input| self.pid.step(input))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inputs.size_hint()
+    }
+}
+
+/// A `PidCtrl` whose error is computed by a closure `error_fn(setpoint, measurement)`,
+/// which may capture state. It is `Clone` when the closure is, but never `Copy`. Any error
+/// function set on `pid` itself is ignored.
+#[derive(Clone)]
+pub struct PidCtrlFlex<T: FloatCore + core::default::Default, F: FnMut(T, T) -> T> {
+    pub pid: PidCtrl<T>,
+    pub error_fn: F,
+}
+
+impl<T: FloatCore + core::default::Default, F: FnMut(T, T) -> T> PidCtrlFlex<T, F> {
+    pub fn new(pid: PidCtrl<T>, error_fn: F) -> Self {
+        PidCtrlFlex{pid, error_fn}
+    }
+
+    pub fn step(&mut self, input: PidIn<T>) -> PidOut<T> {
+        self.pid.step_with_error(input, self.pid.setpoint, &mut self.error_fn).base
+    }
+}
+
+/// Velocity (incremental) form PID: each step adds
+/// `kp * (e[k] - e[k-1]) + ki * e[k] * tdelta + kd * (e[k] - 2 * e[k-1] + e[k-2]) / tdelta`
+/// to the previous output. With `limits` applied to the stored output there is no
+/// integrator to wind up. The derivative acts on the error, unlike `PidCtrl`.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct VelocityPidCtrl<T: FloatCore + core::default::Default> {
+    pub kp: T,
+    pub ki: T,
+    pub kd: T,
+    pub limits: Limits<T>,
+    prev_error: T,
+    prev_prev_error: T,
+    prev_output: T,
+}
+
+impl<T: FloatCore + core::default::Default> VelocityPidCtrl<T> {
+    pub fn new(kp: T, ki: T, kd: T) -> Self {
+        VelocityPidCtrl{kp, ki, kd, ..Self::default()}
+    }
+
+    pub fn prev_output(&self) -> T {
+        self.prev_output
+    }
+
+    /// `tdelta` is clamped like `PidIn::effective_tdelta`.
+    pub fn step(&mut self, error: T, tdelta: T) -> T {
+        let tdelta = PidIn::new(error, tdelta).effective_tdelta();
+        let two = T::one() + T::one();
+        let increment = self.kp * (error - self.prev_error)
+            + self.ki * error * tdelta
+            + self.kd * (error - two * self.prev_error + self.prev_prev_error) / tdelta;
+        self.prev_output = self.limits.clamp(self.prev_output + increment);
+        self.prev_prev_error = self.prev_error;
+        self.prev_error = error;
+        self.prev_output
+    }
+}
+
+/// Relay feedback auto-tuning (Åström and Hägglund). While stepped in place of the
+/// controller, drives the plant with `±relay_amplitude` around `setpoint`, switching when
+/// the error leaves `±hysteresis`, and measures the period and amplitude of the resulting
+/// limit cycle. The first cycle is discarded as a transient; estimates average the last
+/// `N` cycles.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct RelayAutoTuner<T: FloatCore + core::default::Default, const N: usize> {
+    pub setpoint: T,
+    relay_amplitude: T,
+    hysteresis: T,
+    output: T,
+    time: T,
+    last_rise: Option<T>,
+    cycle_max: T,
+    cycle_min: T,
+    periods: [T; N],
+    amplitudes: [T; N],
+    cycles: usize,
+}
+
+impl<T: FloatCore + core::default::Default, const N: usize> RelayAutoTuner<T, N> {
+    /// `relay_amplitude` must be positive and `hysteresis` non-negative, both finite.
+    pub fn try_new(setpoint: T, relay_amplitude: T, hysteresis: T) -> Result<Self, PidError> {
+        if !relay_amplitude.is_finite() || relay_amplitude <= T::zero()
+            || !hysteresis.is_finite() || hysteresis < T::zero() {
+            return Err(PidError::InvalidValue);
+        }
+        Ok(RelayAutoTuner{
+            setpoint, relay_amplitude, hysteresis, output: T::zero(), time: T::zero(), last_rise: None,
+            cycle_max: T::neg_infinity(), cycle_min: T::infinity(), periods: [T::zero(); N],
+            amplitudes: [T::zero(); N], cycles: 0,
+        })
+    }
+
+    /// Returns the relay output to apply to the plant. `tdelta` is clamped like
+    /// `PidIn::effective_tdelta`.
+    pub fn step(&mut self, measurement: T, tdelta: T) -> T {
+        self.time = self.time + PidIn::new(measurement, tdelta).effective_tdelta();
+        self.cycle_max = self.cycle_max.max(measurement);
+        self.cycle_min = self.cycle_min.min(measurement);
+        let error = self.setpoint - measurement;
+        if error > self.hysteresis && self.output <= T::zero() {
+            if let Some(last_rise) = self.last_rise {
+                // the cycle before the first complete one still holds the start-up transient
+                if self.cycles > 0 && N > 0 {
+                    let slot = (self.cycles - 1) % N;
+                    self.periods[slot] = self.time - last_rise;
+                    self.amplitudes[slot] = (self.cycle_max - self.cycle_min) / (T::one() + T::one());
+                }
+                self.cycles += 1;
+            }
+            self.last_rise = Some(self.time);
+            self.cycle_max = measurement;
+            self.cycle_min = measurement;
+            self.output = self.relay_amplitude;
+        } else if error < -self.hysteresis && self.output >= T::zero() {
+            self.output = -self.relay_amplitude;
+        }
+        self.output
+    }
+
+    fn mean(&self, values: &[T; N]) -> Option<T> {
+        if N == 0 || self.cycles <= N {
+            return None;
+        }
+        Some(values.iter().fold(T::zero(), |sum, &val| sum + val) / T::from(N).unwrap())
+    }
+
+    /// Mean limit-cycle period, once `N` cycles have been observed.
+    pub fn period_estimate(&self) -> Option<T> {
+        self.mean(&self.periods)
+    }
+
+    /// Mean half peak-to-peak amplitude of the measurement, once `N` cycles have been observed.
+    pub fn amplitude_estimate(&self) -> Option<T> {
+        self.mean(&self.amplitudes)
+    }
+
+    /// Ultimate gain from the describing function of a relay with hysteresis,
+    /// `4 * d / (pi * sqrt(a^2 - hysteresis^2))`. `None` unless the amplitude exceeds the
+    /// hysteresis, for which the describing function is undefined.
+    pub fn ultimate_gain(&self) -> Option<T> {
+        let amplitude = self.amplitude_estimate()?;
+        if amplitude <= self.hysteresis {
+            return None;
+        }
+        let pi = math::cast::<T>(core::f64::consts::PI);
+        let four = math::cast::<T>(4.0);
+        Some(four * self.relay_amplitude / (pi * math::sqrt(amplitude * amplitude - self.hysteresis * self.hysteresis)))
+    }
+
+    /// Classic Ziegler-Nichols PID gains `(kp, ki, kd)` for `PidCtrl::new_with_pid`:
+    /// `kp = 0.6 * ku`, `ti = tu / 2`, `td = tu / 8`.
+    pub fn gains_pid(&self) -> Option<(T, T, T)> {
+        let ku = self.ultimate_gain()?;
+        let tu = self.period_estimate()?;
+        let kp = math::cast::<T>(0.6) * ku;
+        let two = T::one() + T::one();
+        Some((kp, kp * two / tu, kp * tu / math::cast::<T>(8.0)))
+    }
+}
+
+impl<T: FloatCore + core::default::Default> core::ops::Neg for PidOut<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        PidOut::new(-self.p, -self.i, -self.d, -self.out)
+    }
+}
+
+impl<T: FloatCore + core::default::Default> core::ops::Add for PidOut<T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        PidOut::new(self.p + rhs.p, self.i + rhs.i, self.d + rhs.d, self.out + rhs.out)
+    }
+}
+
+impl<T: FloatCore + core::default::Default> core::ops::AddAssign for PidOut<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T: FloatCore + core::default::Default> core::ops::Sub for PidOut<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        PidOut::new(self.p - rhs.p, self.i - rhs.i, self.d - rhs.d, self.out - rhs.out)
+    }
+}
+
+impl<T: FloatCore + core::default::Default> core::ops::SubAssign for PidOut<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<T: FloatCore + core::default::Default> core::iter::Sum for PidOut<T> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(PidOut::default(), |acc, out| acc + out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn limits_error() {
+        let mut pid = super::PidCtrl::new_with_pid(3.0, 2.0, 1.0);
+        pid.kp.limits.try_set_lower(10.0).unwrap();
+        assert_eq!(super::PidError::LimitOutBound, pid.kp.limits.try_set_upper(5.0).unwrap_err());
+    }
+
+    #[test]
+    fn kp() {
+        let kp = 0.2;
+        let measurement = 0.0;
+        let setpoint = 1.0;
+
+        let mut pid = super::PidCtrl::default();
+        pid.init(setpoint, 0.0);
+        pid.kp.set_scale(kp);
 
         let kpterm = kp * (setpoint - measurement);
 
-        let inp = super::PidIn::new(measurement, 1.0);
-        assert_eq!(pid.step(inp), super::PidOut::new(kpterm, 0.0, 0.0, kpterm));
+        let inp = super::PidIn::new(measurement, 1.0);
+        assert_eq!(pid.step(inp), super::PidOut::new(kpterm, 0.0, 0.0, kpterm));
+    }
+
+    #[test]
+    fn ki() {
+        let ki = 1.0;
+        let measurement = 0.0;
+        let setpoint = 1.0;
+        let td = 1.0;
+
+        let mut pid = super::PidCtrl::default();
+        pid.init(setpoint, 0.0);
+        pid.ki.set_scale(ki);
+
+        let mut kiterm = 0.0;
+
+        kiterm += ki * (setpoint - measurement) * td;
+        let inp = super::PidIn::new(measurement, td);
+        assert_eq!(pid.step(inp), super::PidOut::new(0.0, kiterm, 0.0, kiterm));
+
+        kiterm += ki * (setpoint - measurement) * td;
+        let inp = super::PidIn::new(measurement, td);
+        assert_eq!(pid.step(inp), super::PidOut::new(0.0, kiterm, 0.0, kiterm));
+    }
+
+    #[test]
+    fn kd() {
+        let kd = 1.0;
+        let measurement = 0.0;
+        let setpoint = 1.0;
+        let td = 1.0;
+        
+        let mut prev = 0.0;
+
+        let mut pid = super::PidCtrl::default();
+        pid.init(setpoint, prev);
+        pid.kd.set_scale(kd);
+
+        let mut kdterm = kd * (measurement - prev) / td;
+        prev = measurement;
+        let inp = super::PidIn::new(measurement, td);
+        assert_eq!(pid.step(inp), super::PidOut::new(0.0, 0.0, kdterm, kdterm));
+
+        kdterm = kd * (measurement - prev) / td;
+        let inp = super::PidIn::new(measurement, td);
+        assert_eq!(pid.step(inp), super::PidOut::new(0.0, 0.0, kdterm, kdterm));
+    }
+
+    #[test]
+    fn limits_overlap() {
+        let mut outer = super::Limits::default();
+        outer.set_limit(10.0);
+        let mut inner = super::Limits::default();
+        inner.try_set_lower(2.0).unwrap().try_set_upper(5.0).unwrap();
+        let mut disjoint = super::Limits::default();
+        disjoint.try_set_lower(11.0).unwrap();
+
+        assert!(outer.overlaps(&inner) && inner.overlaps(&outer));
+        assert!(inner.is_subset_of(&outer) && !outer.is_subset_of(&inner));
+        assert!(!disjoint.overlaps(&inner));
+        assert!(!disjoint.overlaps(&outer) && !disjoint.is_subset_of(&outer));
+    }
+
+    #[test]
+    fn try_step() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        let invalid = super::PidError::InvalidValue;
+        assert_eq!(pid.try_step(super::PidIn::new(f64::NAN, 1.0)), Err(invalid));
+        assert_eq!(pid.try_step(super::PidIn::new(f64::INFINITY, 1.0)), Err(invalid));
+        assert_eq!(pid.try_step(super::PidIn::new(0.0, 0.0)), Err(invalid));
+        assert_eq!(pid.try_step(super::PidIn::new(0.0, -1.0)), Err(invalid));
+        assert_eq!(pid, super::PidCtrl::new_with_pid(1.0, 1.0, 1.0));
+
+        pid.setpoint = 1.0;
+        assert_eq!(pid.try_step(super::PidIn::new(0.0, 1.0)), Ok(super::PidOut::new(1.0, 1.0, 0.0, 2.0)));
+
+        pid.setpoint = f64::NAN;
+        assert_eq!(pid.try_step(super::PidIn::new(0.0, 1.0)), Err(invalid));
+    }
+
+    #[test]
+    fn pid_term() {
+        use super::{PidTerm, TermKind};
+
+        let mut pid = super::PidCtrl::new_with_pid(3.0, 2.0, 1.0);
+        for which in [TermKind::P, TermKind::I, TermKind::D] {
+            pid.term_mut(which).set_scale_checked(0.0).unwrap();
+            pid.term_mut(which).limits_mut().set_limit(1.0);
+        }
+        assert_eq!(pid.kp.scale(), 0.0);
+        assert_eq!(pid.ki.scale(), 0.0);
+        assert_eq!(pid.term(TermKind::D).scale(), 0.0);
+        assert_eq!(pid.term(TermKind::I).limits(), &pid.kd.limits);
+
+        assert_eq!(pid.kp.set_scale_checked(f64::NAN), Err(super::PidError::InvalidValue));
+        assert_eq!(pid.kd.set_scale_checked(f64::INFINITY), Err(super::PidError::InvalidValue));
+        assert_eq!(pid.kp.scale(), 0.0);
+    }
+
+    #[test]
+    fn pidout_components() {
+        use super::TermKind;
+
+        let out = super::PidOut::new(1.0, -4.0, 2.0, -1.0);
+        assert_eq!(out.dominant_term(), (-4.0, TermKind::I));
+        assert_eq!(out.component_max(), 2.0);
+        assert_eq!(out.component_min(), -4.0);
+        assert_eq!(super::PidOut::new(3.0, 1.0, -3.0, 1.0).dominant_term(), (3.0, TermKind::P));
+    }
+
+    #[test]
+    fn ki_decay() {
+        let mut ki = super::KITerm::new();
+        ki.set_scale(1.0).set_decay_rate(0.5).unwrap();
+        ki.set_accumulate(2.0);
+        let expected = 2.0 * (-0.5f64 * 2.0).exp();
+        assert!((ki.step(0.0, 2.0) - expected).abs() < 1e-12);
+        assert!((ki.step(1.0, 1.0) - (expected * (-0.5f64).exp() + 1.0)).abs() < 1e-12);
+
+        assert_eq!(ki.set_decay_rate(-1.0).unwrap_err(), super::PidError::InvalidValue);
+        assert_eq!(ki.set_decay_rate(f64::NAN).unwrap_err(), super::PidError::InvalidValue);
+    }
+
+    #[test]
+    fn pidout_neg() {
+        let out = super::PidOut::new(1.0, -2.0, 0.5, -0.5);
+        assert_eq!((-out).out, -out.out);
+        assert_eq!(-out, super::PidOut::new(-1.0, 2.0, -0.5, 0.5));
+    }
+
+    #[test]
+    fn copy_gains_and_state() {
+        let mut source = super::PidCtrl::new_with_pid(3.0, 2.0, 1.0);
+        source.limits.set_limit(5.0);
+        source.ki.limits.set_limit(4.0);
+        source.init(1.0, 0.5).step(super::PidIn::new(0.0, 1.0));
+
+        let mut gains = super::PidCtrl::new();
+        gains.init(7.0, 6.0);
+        gains.ki.set_accumulate(-1.0);
+        source.copy_gains_to(&mut gains);
+        assert_eq!((gains.kp, gains.limits, gains.ki.limits), (source.kp, source.limits, source.ki.limits));
+        assert_eq!((gains.setpoint, gains.ki.accumulate(), gains.kd.prev_measurement()), (7.0, -1.0, 6.0));
+
+        let mut state = super::PidCtrl::new();
+        state.setpoint = 7.0;
+        source.copy_state_to(&mut state);
+        assert_eq!((state.ki.accumulate(), state.kd.prev_measurement()), (2.0, 0.0));
+        assert_eq!((state.setpoint, state.kp, state.limits), (7.0, super::KPTerm::new(), super::Limits::default()));
+    }
+
+    #[test]
+    fn limits_from_center() {
+        let limits = super::Limits::try_from_center(2.5, 1.5).unwrap();
+        assert_eq!((limits.lower(), limits.upper()), (1.0, 4.0));
+        assert_eq!((limits.center(), limits.half_width()), (2.5, Some(1.5)));
+        assert_eq!(limits, super::Limits::try_new(1.0, 4.0).unwrap());
+
+        let invalid = super::PidError::InvalidValue;
+        assert_eq!(super::Limits::try_from_center(0.0, -1.0), Err(invalid));
+        assert_eq!(super::Limits::try_from_center(f64::NAN, 1.0), Err(invalid));
+        assert_eq!(super::Limits::try_from_center(0.0, f64::NAN), Err(invalid));
+        assert_eq!(super::Limits::try_new(1.0, 0.0), Err(super::PidError::LimitOutBound));
+        assert_eq!(super::Limits::<f64>::default().half_width(), None);
+    }
+
+    #[test]
+    fn step_count_and_reset() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        pid.init(1.0, 0.5);
+        for _ in 0..3 {
+            pid.step(super::PidIn::new(0.0, 1.0));
+        }
+        assert_eq!(pid.step_count(), 3);
+
+        pid.reset();
+        assert_eq!((pid.step_count(), pid.ki.accumulate(), pid.kd.prev_measurement()), (0, 0.0, 0.0));
+        assert_eq!(pid.setpoint, 1.0);
+    }
+
+    #[test]
+    fn kd_ewma() {
+        let noisy = [0.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0];
+        let total_variation = |alpha: f64| {
+            let mut kd = super::KDTerm::new();
+            kd.set_scale(1.0).set_ewma_alpha(alpha).unwrap();
+            noisy.iter().map(|m| kd.step(*m, 1.0).abs()).sum::<f64>()
+        };
+        assert!(total_variation(0.1) < 0.2 * total_variation(1.0));
+
+        let mut kd = super::KDTerm::<f64>::new();
+        assert_eq!(kd.set_ewma_alpha(0.0).unwrap_err(), super::PidError::InvalidValue);
+        assert_eq!(kd.set_ewma_alpha(1.5).unwrap_err(), super::PidError::InvalidValue);
+        assert_eq!(kd.set_ewma_alpha(f64::NAN).unwrap_err(), super::PidError::InvalidValue);
+    }
+
+    #[test]
+    fn kp_setpoint_weight() {
+        let kick = |b: f64| {
+            let mut pid = super::PidCtrl::new_with_pid(2.0, 0.0, 0.0);
+            pid.kp.set_setpoint_weight(b).unwrap();
+            pid.init(0.0, 1.0);
+            pid.step(super::PidIn::new(1.0, 1.0));
+            pid.set_setpoint(5.0);
+            pid.step(super::PidIn::new(1.0, 1.0)).p
+        };
+        assert_eq!(kick(1.0), 8.0);
+        assert_eq!(kick(0.5), 3.0);
+        assert_eq!(kick(0.0), -2.0);
+
+        let mut kp = super::KPTerm::<f64>::new();
+        assert_eq!(kp.set_setpoint_weight(-0.1).unwrap_err(), super::PidError::InvalidValue);
+        assert_eq!(kp.set_setpoint_weight(f64::NAN).unwrap_err(), super::PidError::InvalidValue);
+    }
+
+    #[test]
+    fn pidout_array_tuple() {
+        let out = super::PidOut::new(1.0, 2.0, 3.0, 6.0);
+        assert_eq!(out.to_array(), [1.0, 2.0, 3.0, 6.0]);
+        assert_eq!(out.to_tuple(), (1.0, 2.0, 3.0, 6.0));
+        assert_eq!(super::PidOut::from_array(out.to_array()), out);
+        assert_eq!(super::PidOut::from_tuple(out.to_tuple()), out);
+    }
+
+    #[test]
+    fn error_fn() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 0.0);
+        pid.set_error_fn(|setpoint, measurement| measurement - setpoint).init(1.0, 0.0);
+        assert_eq!(pid.step(super::PidIn::new(3.0, 1.0)), super::PidOut::new(2.0, 2.0, 0.0, 4.0));
+
+        let mut copy = super::PidCtrl::new();
+        pid.copy_gains_to(&mut copy);
+        copy.init(1.0, 0.0);
+        assert_eq!(copy.step(super::PidIn::new(3.0, 1.0)).p, 2.0);
+    }
+
+    #[test]
+    fn limits_try_clamp_finite() {
+        let limits = super::Limits::try_new(-1.0, 1.0).unwrap();
+        assert_eq!(limits.try_clamp_finite(5.0), Ok(1.0));
+        assert_eq!(limits.try_clamp_finite(0.5), Ok(0.5));
+        assert_eq!(limits.try_clamp_finite(f64::NAN), Err(super::PidError::InvalidValue));
+        assert_eq!(limits.try_clamp_finite(f64::NEG_INFINITY), Err(super::PidError::InvalidValue));
+    }
+
+    #[test]
+    fn error_stats() {
+        let mut pid = super::PidCtrl::<f64>::new();
+        pid.setpoint = 1.0;
+        for measurement in [0.0, 2.0, 4.0, -1.0] {
+            pid.step(super::PidIn::new(measurement, 1.0));
+        }
+        // errors are 1, -1, -3, 2
+        let stats = *pid.error_stats();
+        assert_eq!((stats.count, stats.mean, stats.min, stats.max), (4, -0.25, -3.0, 2.0));
+        assert!((stats.variance - 3.6875).abs() < 1e-12);
+
+        pid.reset();
+        assert_eq!(*pid.error_stats(), super::ErrorStats::default());
+    }
+
+    #[test]
+    fn kd_initial_derivative() {
+        let mut kd = super::KDTerm::new();
+        kd.set_scale(2.0).set_initial_derivative(3.0);
+        kd.set_prev_measurement(10.0);
+        // the preloaded rate replaces the kick from prev_measurement = 10
+        assert_eq!(kd.step(0.0, 1.0), -6.0);
+        assert_eq!(kd.prev_measurement(), 0.0);
+        assert_eq!(kd.step(1.0, 0.5), -4.0);
+    }
+
+    #[test]
+    fn standard_form() {
+        let pid = super::PidCtrl::new_with_standard_form(2.0, 4.0, 0.25);
+        assert_eq!(pid, super::PidCtrl::new_with_pid(2.0, 0.5, 0.5));
+        assert_eq!(pid.gains_standard_form(), (2.0, 4.0, 0.25));
+
+        let pi = super::PidCtrl::new_with_standard_form(2.0, f64::INFINITY, 0.0);
+        assert_eq!(pi, super::PidCtrl::new_with_pid(2.0, 0.0, 0.0));
+        assert_eq!(pi.gains_standard_form(), (2.0, f64::INFINITY, 0.0));
+
+        let (kp, ti, td) = super::PidCtrl::new_with_pid(0.0f64, 1.0, 1.0).gains_standard_form();
+        assert!(kp == 0.0 && ti.is_nan() && td.is_nan());
+    }
+
+    #[test]
+    fn setpoint_schedule() {
+        let schedule = super::SetpointSchedule::new([(1.0, 2.0), (5.0, 1.0), (-1.0, 0.5)]);
+        assert_eq!(schedule.total_duration(), 3.5);
+        assert_eq!(schedule.setpoint_at(0.0), Some(1.0));
+        assert_eq!(schedule.setpoint_at(2.0), Some(5.0));
+        assert_eq!(schedule.setpoint_at(3.2), Some(-1.0));
+        assert_eq!(schedule.setpoint_at(100.0), Some(-1.0));
+        assert_eq!(super::SetpointSchedule::<f64, 0>::new([]).setpoint_at(0.0), None);
+
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        assert_eq!(pid.step_schedule(&schedule, 2.5, 0.0, 0.5).out, 5.0);
+        assert_eq!(pid.setpoint, 5.0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn run_schedule() {
+        let schedule = super::SetpointSchedule::new([(1.0, 1.0), (3.0, 1.0)]);
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        let outs = pid.run_schedule(&schedule, &[0.0, 0.0, 0.0, 1.0, 0.0], 0.5);
+        let p: std::vec::Vec<f64> = outs.iter().map(|out| out.p).collect();
+        assert_eq!(p, [1.0, 1.0, 3.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn pidout_approx_eq() {
+        let out = super::PidOut::new(0.1 + 0.2, 1.0, f64::INFINITY, 2.0);
+        assert!(out.approx_eq(&super::PidOut::new(0.3, 1.0, f64::INFINITY, 2.0), 1e-12));
+        assert!(!out.approx_eq(&super::PidOut::new(0.3, 1.1, f64::INFINITY, 2.0), 1e-12));
+        assert!(!out.approx_eq(&super::PidOut::new(0.3, 1.0, f64::NEG_INFINITY, 2.0), 1e-12));
+    }
+
+    #[test]
+    fn pid_history() {
+        let mut history = super::PidHistory::<f64, 3>::new();
+        assert!(history.is_empty() && history.mean_out().is_nan());
+        for out in [1.0, 5.0, -2.0, 4.0] {
+            history.push(super::PidOut::new(0.0, 0.0, 0.0, out));
+        }
+        let outs: [f64; 3] = core::array::from_fn(|k| history.iter().nth(k).unwrap().out);
+        assert_eq!((history.len(), outs), (3, [5.0, -2.0, 4.0]));
+        assert_eq!((history.mean_out(), history.max_out(), history.min_out()), (7.0 / 3.0, 5.0, -2.0));
+
+        let mut empty = super::PidHistory::<f64, 0>::new();
+        empty.push(super::PidOut::default());
+        assert_eq!(empty.iter().count(), 0);
+
+        let mut pid = super::PidCtrlWithHistory::<f64, 2>::new(super::PidCtrl::new_with_pid(1.0, 0.0, 0.0));
+        pid.pid.setpoint = 1.0;
+        let out = pid.step(super::PidIn::new(0.0, 1.0));
+        assert_eq!(pid.history.iter().next(), Some(&out));
+    }
+
+    #[test]
+    fn ki_anti_windup() {
+        let overshoot = |anti_windup: bool| {
+            let mut ki = super::KITerm::new();
+            ki.set_scale(1.0).set_anti_windup(anti_windup);
+            for _ in 0..5 {
+                ki.step(1.0, 1.0);
+            }
+            // the measurement overshoots the setpoint
+            ki.step(-0.5, 1.0)
+        };
+        assert_eq!(overshoot(false), 4.5);
+        assert_eq!(overshoot(true), 5.0);
+    }
+
+    #[test]
+    fn anti_windup_settles_sooner() {
+        // steps of an underdamped second-order plant until it stays within 2% of the setpoint
+        let settling_steps = |anti_windup: bool| {
+            let mut pid = super::PidCtrl::new_with_pid(4.0, 1.0, 0.0);
+            pid.ki.set_anti_windup(anti_windup);
+            pid.setpoint = 1.0;
+            let (wn, zeta, dt) = (2.0, 0.3, 0.01);
+            let (mut y, mut v, mut settled) = (0.0f64, 0.0, 0);
+            for k in 0..5000 {
+                let u = pid.step(super::PidIn::new(y, dt)).out;
+                v += (wn * wn * (u - y) - 2.0 * zeta * wn * v) * dt;
+                y += v * dt;
+                if (y - 1.0).abs() > 0.02 {
+                    settled = k + 1;
+                }
+            }
+            settled
+        };
+        // the integrator no longer unwinds on each overshoot, only to wind up again after
+        assert!(settling_steps(true) < settling_steps(false));
+    }
+
+    #[test]
+    fn sample_period_normalization() {
+        let mut pid = super::PidCtrl::new_with_pid(2.0, 0.1, 5.0);
+        assert_eq!(pid.to_continuous_gains(0.1), (2.0, 1.0, 0.5));
+        pid.normalize_to_sample_period(0.5, 0.1);
+        assert_eq!(pid.to_continuous_gains(0.5), (2.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn warm_start() {
+        let mut active = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        active.init(2.0, 0.0);
+        active.step(super::PidIn::new(1.0, 1.0));
+
+        let mut standby = super::PidCtrl::new_with_pid(2.0, 0.5, 0.0);
+        standby.setpoint = 3.0;
+        assert!(!standby.is_warmed_up());
+        standby.warm_start_from(&active);
+        assert!(standby.is_warmed_up());
+        assert_eq!((standby.ki.accumulate(), standby.kd.prev_measurement(), standby.step_count()), (1.0, 1.0, 1));
+        assert_eq!((standby.setpoint, standby.kp.step(1.0)), (3.0, 2.0));
+
+        standby.reset();
+        assert!(!standby.is_warmed_up());
     }
 
     #[test]
-    fn ki() {
-        let ki = 1.0;
-        let measurement = 0.0;
-        let setpoint = 1.0;
-        let td = 1.0;
+    fn copy_gains_and_state_roundtrip() {
+        let mut active = super::PidCtrl::new_with_pid(2.0, 1.0, 0.5);
+        active.limits.set_limit(3.0);
+        active.ki.limits.set_limit(1.0);
+        active.kd.set_ewma_alpha(0.5).unwrap();
+        active.set_measurement_filter(super::NotchFilter::try_new(0.2, 0.1).unwrap());
+        active.set_quantization_step(0.125).unwrap();
+        active.set_tracking_gain(0.5).unwrap();
+        active.set_integral_reset_threshold(2.0).unwrap();
+        active.set_convergence_criteria(0.5, 0.75);
+        active.set_sample_period(0.5).unwrap();
+        active.set_setpoint_limits(super::Limits::try_new(-1.0, 0.5).unwrap(), super::SetpointLimitMode::Clamp);
+        active.set_error_fn(|setpoint: f64, measurement: f64| 2.0 * (setpoint - measurement));
+        active.init(1.0, 0.0);
+        active.step(super::PidIn::new(0.5, 1.0));
 
-        let mut pid = super::PidCtrl::default();
-        pid.init(setpoint, 0.0);
-        pid.ki.set_scale(ki);
+        let mut standby = super::PidCtrl::new();
+        active.copy_gains_to(&mut standby);
+        active.copy_state_to(&mut standby);
+        standby.setpoint = active.setpoint;
+        assert_eq!(standby.sample_period(), active.sample_period());
+        assert_eq!(standby.setpoint_limits(), active.setpoint_limits());
+        assert_eq!(standby.last_output(), active.last_output());
+        for measurement in [0.25, 1.5, 1.0] {
+            let input = super::PidIn::new(measurement, 0.5);
+            assert_eq!(standby.step(input), active.step(input));
+            assert_eq!(standby.is_converged(), active.is_converged());
+        }
+    }
 
-        let mut kiterm = 0.0;
+    #[test]
+    fn pidout_lerp() {
+        let a = super::PidOut::new(1.0, 2.0, 3.0, 6.0);
+        let b = super::PidOut::new(3.0, -2.0, 0.0, 1.0);
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        assert_eq!(a.lerp(&b, 0.5), super::PidOut::new(2.0, 0.0, 1.5, 3.5));
+        assert_eq!(a.lerp(&b, 7.0), b);
+    }
 
-        kiterm += ki * (setpoint - measurement) * td;
-        let inp = super::PidIn::new(measurement, td);
-        assert_eq!(pid.step(inp), super::PidOut::new(0.0, kiterm, 0.0, kiterm));
+    #[test]
+    fn limits_valid() {
+        assert!(super::Limits::<f64>::default().is_valid());
+        assert!(!super::Limits{lower: 1.0, upper: 0.0}.is_valid());
+        assert!(!super::Limits{lower: f64::NAN, upper: 0.0}.is_valid());
+        super::Limits::try_new(0.0, 0.0).unwrap().assert_valid();
+    }
 
-        kiterm += ki * (setpoint - measurement) * td;
-        let inp = super::PidIn::new(measurement, td);
-        assert_eq!(pid.step(inp), super::PidOut::new(0.0, kiterm, 0.0, kiterm));
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Limits: lower bound is NaN")]
+    fn limits_clamp_asserts_valid() {
+        let mut kp = super::KPTerm::new();
+        kp.set_scale(1.0).limits.set_limit(f64::NAN);
+        kp.step(1.0);
     }
 
     #[test]
-    fn kd() {
-        let kd = 1.0;
-        let measurement = 0.0;
-        let setpoint = 1.0;
-        let td = 1.0;
-        
-        let mut prev = 0.0;
+    fn all_limits() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        pid.set_all_limits(4.0, 3.0, 2.0, 1.0);
+        let symmetric = |val: f64| super::Limits::try_new(-val, val).unwrap();
+        assert_eq!(pid.limits, symmetric(4.0));
+        assert_eq!(pid.kp.limits, symmetric(3.0));
+        assert_eq!(pid.ki.limits, symmetric(2.0));
+        assert_eq!(pid.kd.limits, symmetric(1.0));
 
-        let mut pid = super::PidCtrl::default();
-        pid.init(setpoint, prev);
-        pid.kd.set_scale(kd);
+        pid.clear_all_limits();
+        assert_eq!(pid, super::PidCtrl::new_with_pid(1.0, 1.0, 1.0));
+    }
 
-        let mut kdterm = kd * (measurement - prev) / td;
-        prev = measurement;
-        let inp = super::PidIn::new(measurement, td);
-        assert_eq!(pid.step(inp), super::PidOut::new(0.0, 0.0, kdterm, kdterm));
+    #[test]
+    fn kd_second_order() {
+        let acceleration = 3.0;
+        let td = 0.5;
+        let mut pid = super::PidCtrl::new_with_pid(0.0, 0.0, 2.0);
+        pid.kd.set_second_order(true);
+        pid.init(0.0, 0.0);
+        for k in 1..6 {
+            let t = k as f64 * td;
+            let d = pid.step(super::PidIn::new(0.5 * acceleration * t * t, td)).d;
+            if k > 1 {
+                assert!((d + 2.0 * acceleration).abs() < 1e-9);
+            }
+        }
+    }
 
-        kdterm = kd * (measurement - prev) / td;
-        let inp = super::PidIn::new(measurement, td);
-        assert_eq!(pid.step(inp), super::PidOut::new(0.0, 0.0, kdterm, kdterm));
+    #[test]
+    fn manual_bumpless_transfer() {
+        let mut pid = super::PidCtrl::new_with_pid(2.0_f64, 0.5, 0.0);
+        pid.init(10.0, 4.0);
+        pid.set_manual(7.0);
+        for _ in 0..5 {
+            assert_eq!(pid.step(super::PidIn::new(4.0, 1.0)).out, 7.0);
+        }
+        assert_eq!(pid.ki.accumulate(), 7.0 - 2.0 * 6.0);
+        pid.set_automatic();
+        assert!(pid.manual_output().is_none());
+        let out = pid.step(super::PidIn::new(4.0, 1e-9)).out;
+        assert!((out - 7.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ki_max_step() {
+        let mut ki = super::KITerm::new();
+        ki.set_scale(2.0);
+        assert_eq!(ki.set_max_step(0.0), Err(super::PidError::InvalidValue));
+        assert_eq!(ki.set_max_step(f64::NAN), Err(super::PidError::InvalidValue));
+        ki.set_max_step(5.0).unwrap();
+        assert_eq!(ki.step(1.0, 1000.0), 5.0);
+        assert_eq!(ki.step(-1.0, 1000.0), 0.0);
+        assert_eq!(ki.step(1.0, 0.5), 1.0);
+    }
+
+    #[test]
+    fn pidout_add_assign() {
+        let a = super::PidOut::new(1.0, 2.0, 3.0, 6.0);
+        let b = super::PidOut::new(0.5, -1.0, 0.25, -0.25);
+        let mut total = a;
+        total += b;
+        assert_eq!(total, a + b);
+        total -= b;
+        assert_eq!(total, a);
+        let sum: super::PidOut<f64> = [a, b, a].iter().copied().sum();
+        assert_eq!(sum, a + b + a);
+    }
+
+    #[test]
+    fn ki_integration_sign() {
+        let mut ki = super::KITerm::new();
+        ki.set_scale(1.0).set_integration_sign(super::IntegrationSign::PositiveOnly);
+        assert_eq!(ki.step(2.0, 1.0), 2.0);
+        assert_eq!(ki.step(-5.0, 1.0), 2.0);
+        ki.set_integration_sign(super::IntegrationSign::NegativeOnly);
+        assert_eq!(ki.step(3.0, 1.0), 2.0);
+        assert_eq!(ki.step(-0.5, 1.0), 1.5);
+        ki.set_integration_sign(super::IntegrationSign::default());
+        assert_eq!(ki.step(0.5, 1.0), 2.0);
+    }
+
+    #[test]
+    fn last_output() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 0.0);
+        assert_eq!(pid.last_output(), super::PidOut::default());
+        pid.setpoint = 2.0;
+        let out = pid.step(super::PidIn::new(0.0, 1.0));
+        assert_eq!(pid.last_output(), out);
+        let prev = pid.last_output().out;
+        assert_eq!(pid.step(super::PidIn::new(0.0, 1.0)).out - prev, 2.0);
+        pid.reset();
+        assert_eq!(pid.last_output(), super::PidOut::default());
+    }
+
+    #[test]
+    fn notch_filter() {
+        assert_eq!(super::NotchFilter::try_new(0.0, 1.0), Err(super::PidError::InvalidValue));
+        assert_eq!(super::NotchFilter::try_new(10.0, f64::NAN), Err(super::PidError::InvalidValue));
+        let td = 1e-3;
+        let tone = |freq: f64| {
+            let mut filter = super::NotchFilter::try_new(50.0, 5.0).unwrap();
+            let mut peak: f64 = 0.0;
+            for k in 0..4000 {
+                let x = (2.0 * core::f64::consts::PI * freq * k as f64 * td).sin();
+                let y = filter.filter(x, td);
+                if k >= 3000 {
+                    peak = peak.max(y.abs());
+                }
+            }
+            peak
+        };
+        assert!(tone(50.0) < 0.01);
+        assert!(tone(5.0) > 0.95);
+
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        pid.set_measurement_filter(super::NotchFilter::try_new(50.0, 5.0).unwrap());
+        pid.init(3.0, 1.0);
+        assert!((pid.step(super::PidIn::new(1.0, td)).p - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn try_set_scale() {
+        let mut ki = super::KITerm::new();
+        assert_eq!(ki.try_set_scale(f64::NAN).unwrap_err(), super::PidError::InvalidValue);
+        assert_eq!(ki.try_set_scale(f64::NEG_INFINITY).unwrap_err(), super::PidError::InvalidValue);
+        ki.try_set_scale(2.5).unwrap().set_anti_windup(true);
+        assert_eq!(super::PidTerm::scale(&ki), 2.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "gains must be finite")]
+    fn new_with_pid_nan() {
+        super::PidCtrl::new_with_pid(1.0, f64::NAN, 0.0);
+    }
+
+    #[test]
+    fn step_with_timing() {
+        assert_eq!(super::PidIn::new(0.0, 0.25).effective_tdelta(), 0.25);
+        assert_eq!(super::PidIn::new(0.0, -3.0).effective_tdelta(), f64::EPSILON);
+        let mut pid = super::PidCtrl::new_with_pid(0.0, 1.0, 0.0);
+        pid.setpoint = 1.0;
+        let (out, tdelta) = pid.step_with_timing(super::PidIn::new(0.0, -1.0));
+        assert_eq!((out.i, tdelta), (f64::EPSILON, f64::EPSILON));
+    }
+
+    #[test]
+    fn ki_split_accumulation() {
+        let mut ki = super::KITerm::new();
+        ki.set_scale(1.0).set_split_accumulation(true);
+        ki.limits = super::Limits::try_new(-2.0, 5.0).unwrap();
+        assert_eq!(ki.step(4.0, 1.0), 4.0);
+        assert_eq!(ki.step(-5.0, 1.0), 2.0);
+        assert_eq!((ki.accumulate_positive(), ki.accumulate_negative()), (4.0, -2.0));
+        assert_eq!(ki.step(3.0, 1.0), 3.0);
+        ki.set_accumulate(-1.0);
+        assert_eq!((ki.accumulate_positive(), ki.accumulate_negative()), (0.0, -1.0));
+
+        let mut plain = super::KITerm::new();
+        plain.set_scale(1.0).limits = super::Limits::try_new(-2.0, 5.0).unwrap();
+        plain.step(4.0, 1.0);
+        assert_eq!(plain.step(-5.0, 1.0), -1.0);
+    }
+
+    #[test]
+    fn pid_ctrl_flex() {
+        let calls = core::cell::Cell::new(0);
+        let deadband = 0.5;
+        let mut pid = super::PidCtrlFlex::new(super::PidCtrl::new_with_pid(2.0, 0.0, 0.0), |sp: f64, m: f64| {
+            calls.set(calls.get() + 1);
+            let e = sp - m;
+            if e.abs() < deadband { 0.0 } else { e }
+        });
+        pid.pid.setpoint = 1.0;
+        assert_eq!(pid.step(super::PidIn::new(0.75, 1.0)).out, 0.0);
+        assert_eq!(pid.step(super::PidIn::new(0.0, 1.0)).out, 2.0);
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn limits_scale_by() {
+        let limits = super::Limits::try_new(-1.0, 3.0).unwrap();
+        assert_eq!(limits.scale_by(2.0), super::Limits::try_new(-2.0, 6.0).unwrap());
+        assert_eq!(limits.scale_by(-1.0), super::Limits::try_new(-3.0, 1.0).unwrap());
+        assert_eq!(super::Limits::<f64>::default().scale_by(0.0), super::Limits::try_new(0.0, 0.0).unwrap());
+        assert!(!limits.scale_by(f64::NAN).is_valid());
+    }
+
+    #[test]
+    fn quantized_output() {
+        let mut pid = super::PidCtrl::new_with_pid(10.0_f64, 0.0, 0.0);
+        assert_eq!(pid.set_quantization_step(0.0).unwrap_err(), super::PidError::InvalidValue);
+        pid.set_quantization_step(1.0).unwrap();
+        pid.limits = super::Limits::try_new(0.0, 255.0).unwrap();
+        pid.setpoint = 20.0;
+        let out = pid.step(super::PidIn::new(6.66, 1.0));
+        assert!((out.p - 133.4).abs() < 1e-9);
+        assert_eq!(out.out, 133.0);
+        assert_eq!(pid.step(super::PidIn::new(6.64, 1.0)).out, 134.0);
+        assert_eq!(pid.step(super::PidIn::new(-50.0, 1.0)).out, 255.0);
+        pid.clear_quantization();
+        assert!((pid.step(super::PidIn::new(6.66, 1.0)).out - 133.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn back_calculation() {
+        let run = |tracking_gain: f64| {
+            let mut pid = super::PidCtrl::new_with_pid(0.5, 1.0, 0.0);
+            pid.set_tracking_gain(tracking_gain).unwrap();
+            pid.limits.set_limit(1.0);
+            pid.setpoint = 1.0;
+            for _ in 0..200 {
+                pid.step(super::PidIn::new(0.0, 0.1));
+            }
+            pid.ki.accumulate()
+        };
+        assert!((run(0.0) - 20.0).abs() < 1e-9);
+        // settles where ki * e + kt * (1 - (p + i)) = 0
+        assert!((run(4.0) - (0.5 + 1.0 / 4.0)).abs() < 1e-6);
+        let mut ki = super::KITerm::new();
+        ki.set_scale(1.0);
+        assert_eq!(ki.step_with_back_calc(2.0, 0.5, -1.0), 0.5);
+        assert_eq!(super::PidCtrl::<f64>::new().set_tracking_gain(-1.0).unwrap_err(), super::PidError::InvalidValue);
+    }
+
+    #[test]
+    fn integral_reset_threshold() {
+        let mut pid = super::PidCtrl::new_with_pid(0.0, 1.0, 0.0);
+        assert_eq!(pid.set_integral_reset_threshold(f64::NAN).unwrap_err(), super::PidError::InvalidValue);
+        pid.set_integral_reset_threshold(2.0).unwrap();
+        pid.ki.set_accumulate(10.0);
+        pid.setpoint = 2.0;
+        assert_eq!(pid.step(super::PidIn::new(0.0, 1.0)).i, 12.0);
+        assert_eq!(pid.step(super::PidIn::new(-0.5, 1.0)).i, 2.5);
+    }
+
+    #[test]
+    fn step_on_change() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 0.0);
+        pid.init(5.0, 1.0);
+        assert_eq!(pid.step_on_change(super::PidIn::new(1.05, 1.0), 0.1), None);
+        assert_eq!(pid.step_count(), 0);
+        let out = pid.step_on_change(super::PidIn::new(1.5, 1.0), 0.1).unwrap();
+        assert_eq!((out.p, out.i), (3.5, 3.5));
+        assert_eq!(pid.kd.prev_measurement(), 1.5);
+    }
+
+    #[test]
+    fn limits_from_range_full() {
+        let mut pid = super::PidCtrl::new_with_pid(0.0, 1.0, 0.0);
+        pid.ki.limits.set_limit(1.0);
+        pid.ki.limits = super::Limits::from(..);
+        assert_eq!(pid.ki.limits, super::Limits::<f64>::default());
+    }
+
+    #[test]
+    fn time_since_setpoint_change() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        pid.step(super::PidIn::new(0.0, 0.5));
+        pid.step(super::PidIn::new(0.0, 0.5));
+        assert_eq!(pid.time_since_setpoint_change(), 1.0);
+        pid.set_setpoint(2.0);
+        pid.step(super::PidIn::new(0.0, 0.25));
+        assert_eq!(pid.time_since_setpoint_change(), 0.25);
+        pid.reset();
+        assert_eq!(pid.time_since_setpoint_change(), 0.0);
+    }
+
+    #[test]
+    fn kd_stencil() {
+        let m = |t: f64| 1.5 * t * t - 2.0 * t + 0.5;
+        let dm = |t: f64| 3.0 * t - 2.0;
+        let td = 0.1;
+        for (stencil, lag) in [(super::DiffStencil::ThreePointCentral, 1.0), (super::DiffStencil::FivePointCentral, 2.0)] {
+            let mut kd = super::KDTerm::new();
+            kd.set_scale(2.0).set_stencil(stencil);
+            for k in 0..10 {
+                let t = k as f64 * td;
+                let d = kd.step(m(t), td);
+                if k >= 4 {
+                    assert!((d + 2.0 * dm(t - lag * td)).abs() < 1e-12);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_version() {
+        let pid = super::PidCtrl::new_with_pid(1.0, 2.0, 3.0);
+        let cfg = pid.to_config();
+        assert_eq!(cfg.version, super::PidCtrl::<f64>::VERSION);
+        assert_eq!(super::PidCtrl::from_config(cfg), Ok(pid));
+        let stale = super::PidCtrlConfig{version: 0, ..cfg};
+        assert_eq!(super::PidCtrl::from_config(stale), Err(super::PidError::VersionMismatch));
+    }
+
+    #[test]
+    fn velocity_matches_position_form() {
+        let (kp, ki, kd, td) = (2.0, 0.5, 0.1, 0.1);
+        let mut position = super::PidCtrl::new_with_pid(kp, ki, kd);
+        position.init(0.0, 0.0);
+        let mut velocity = super::VelocityPidCtrl::new(kp, ki, kd);
+        for k in 1..50 {
+            let measurement = (k as f64 * 0.3).sin();
+            let out = position.step(super::PidIn::new(measurement, td)).out;
+            assert!((velocity.step(-measurement, td) - out).abs() < 1e-9);
+        }
+        velocity.limits.set_limit(1.0);
+        assert_eq!(velocity.step(-100.0, td), -1.0);
+        assert!(velocity.step(100.0, td) > 0.0);
+    }
+
+    #[test]
+    fn step_with_limits() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        pid.limits.set_limit(10.0);
+        pid.setpoint = 8.0;
+        let ramp = super::Limits::try_new(-2.0, 2.0).unwrap();
+        assert_eq!(pid.step_with_limits(super::PidIn::new(0.0, 1.0), &ramp).out, 2.0);
+        assert_eq!(pid.limits, super::Limits::try_new(-10.0, 10.0).unwrap());
+        assert_eq!(pid.step(super::PidIn::new(0.0, 1.0)).out, 8.0);
+    }
+
+    #[test]
+    fn ki_windup_amount() {
+        let mut ki = super::KITerm::new();
+        ki.set_scale(1.0).limits.set_limit(3.0);
+        assert_eq!(ki.step(2.0, 1.0), 2.0);
+        assert_eq!(ki.windup_amount(), 0.0);
+        assert_eq!(ki.step(2.5, 1.0), 3.0);
+        assert_eq!((ki.raw_accumulate(), ki.windup_amount()), (4.5, 1.5));
+        ki.step(-10.0, 1.0);
+        assert_eq!(ki.windup_amount(), -4.0);
+        ki.set_accumulate(0.0);
+        assert_eq!((ki.raw_accumulate(), ki.windup_amount()), (0.0, 0.0));
+    }
+
+    #[test]
+    fn effective_output_range() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        assert_eq!(pid.effective_output_range(), super::Limits::default());
+        pid.set_all_limits(10.0, 2.0, 3.0, 1.0);
+        assert_eq!(pid.effective_output_range(), super::Limits::try_new(-6.0, 6.0).unwrap());
+        pid.limits = super::Limits::try_new(-1.0, 20.0).unwrap();
+        assert_eq!(pid.effective_output_range(), super::Limits::try_new(-1.0, 6.0).unwrap());
+    }
+
+    #[test]
+    fn pidout_fractions() {
+        let out = super::PidOut::new(3.0, 2.0, -1.0, 4.0);
+        assert_eq!((out.p_fraction(), out.i_fraction(), out.d_fraction()), (0.75, 0.5, -0.25));
+        let zero = super::PidOut::new(1.0, -1.0, 0.0, 0.0);
+        assert_eq!((zero.p_fraction(), zero.i_fraction(), zero.d_fraction()), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn pidin_uncertainty() {
+        assert_eq!(super::PidIn::new(1.0, 0.1).uncertainty(), 0.0);
+        let input = super::PidIn::new_with_uncertainty(1.0, 0.1, 0.04);
+        assert_eq!(input.uncertainty(), 0.04);
+        let mut a = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        let mut b = a;
+        assert_eq!(a.step(input), b.step(super::PidIn::new(1.0, 0.1)));
+    }
+
+    #[test]
+    fn step_with_setpoint() {
+        let mut pid = super::PidCtrl::new_with_pid(2.0, 0.0, 0.0);
+        pid.setpoint = 1.0;
+        pid.step(super::PidIn::new(0.0, 1.0));
+        assert_eq!(pid.step_with_setpoint(super::PidIn::new(0.0, 1.0), 3.0).out, 6.0);
+        assert_eq!(pid.setpoint, 1.0);
+        assert_eq!(pid.time_since_setpoint_change(), 2.0);
+    }
+
+    #[test]
+    fn is_converged() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        pid.setpoint = 1.0;
+        pid.step(super::PidIn::new(1.0, 1.0));
+        assert!(!pid.is_converged());
+        pid.set_convergence_criteria(0.1, 2.0);
+        for measurement in [0.95, 1.05] {
+            pid.step(super::PidIn::new(measurement, 1.0));
+        }
+        assert!(pid.is_converged());
+        pid.step(super::PidIn::new(0.5, 1.0));
+        assert!(!pid.is_converged());
+        pid.step(super::PidIn::new(1.0, 1.0));
+        assert!(!pid.is_converged());
+    }
+
+    #[test]
+    fn limits_shift_and_expand() {
+        let mut limits = super::Limits::try_new(-1.0, 3.0).unwrap();
+        limits.shift(2.0);
+        assert_eq!(limits, super::Limits::try_new(1.0, 5.0).unwrap());
+        limits.expand_symmetric(0.5);
+        assert_eq!(limits, super::Limits::try_new(0.5, 5.5).unwrap());
+        limits.expand_symmetric(-10.0);
+        assert_eq!(limits, super::Limits::try_new(3.0, 3.0).unwrap());
+        limits.shift(f64::NAN).expand_symmetric(f64::NAN);
+        assert_eq!(limits, super::Limits::try_new(3.0, 3.0).unwrap());
+    }
+
+    #[test]
+    fn ki_is_near_limit() {
+        let mut ki = super::KITerm::new();
+        ki.set_accumulate(9.5);
+        assert!(!ki.is_near_limit(0.05));
+        ki.limits = super::Limits::try_new(0.0, 10.0).unwrap();
+        assert!(ki.is_near_limit(0.06));
+        assert!(!ki.is_near_limit(0.05));
+        ki.set_accumulate(0.2);
+        assert!(ki.is_near_limit(0.05));
+        ki.set_accumulate(5.0);
+        assert!(!ki.is_near_limit(0.05));
+    }
+
+    #[test]
+    fn pidout_abs_signum() {
+        let out = super::PidOut::new(-2.0, 0.5, 0.0, -1.5);
+        assert_eq!(out.abs_components(), super::PidOut::new(2.0, 0.5, 0.0, 1.5));
+        assert_eq!(out.signum_components(), super::PidOut::new(-1.0, 1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn kd_three_point_backward() {
+        let m = |t: f64| 1.5 * t * t - 2.0 * t + 0.5;
+        let dm = |t: f64| 3.0 * t - 2.0;
+        let td = 0.1;
+        let mut first = super::KDTerm::new();
+        first.set_scale(1.0);
+        let mut second = super::KDTerm::new();
+        second.set_scale(1.0).set_stencil(super::DiffStencil::ThreePointBackward);
+        for k in 0..10 {
+            let t = k as f64 * td;
+            let (d1, d2) = (first.step(m(t), td), second.step(m(t), td));
+            if k >= 2 {
+                assert!((d1 + dm(t)).abs() > 0.1);
+                assert!((d2 + dm(t)).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn relay_auto_tuner_amplitude_within_hysteresis() {
+        let mut tuner = super::RelayAutoTuner::<f64, 3>::try_new(0.0, 1.0, 0.5).unwrap();
+        tuner.cycles = 4;
+        tuner.periods = [2.0; 3];
+        for amplitude in [0.5, 0.25] {
+            tuner.amplitudes = [amplitude; 3];
+            assert_eq!(tuner.ultimate_gain(), None);
+            assert_eq!(tuner.gains_pid(), None);
+        }
+        tuner.amplitudes = [1.0; 3];
+        assert!(tuner.ultimate_gain().unwrap().is_finite());
+    }
+
+    #[test]
+    fn relay_auto_tuner() {
+        // an integrator with dead time L oscillates with period 4 * L and amplitude d * L
+        const DELAY: usize = 500;
+        let td = 0.001;
+        let mut tuner = super::RelayAutoTuner::<f64, 3>::try_new(0.0, 1.0, 0.0).unwrap();
+        assert!(super::RelayAutoTuner::<f64, 3>::try_new(0.0, 0.0, 0.0).is_err());
+        let mut delay = [0.0; DELAY];
+        let mut measurement = 0.1;
+        for k in 0..20_000 {
+            let out = tuner.step(measurement, td);
+            measurement += delay[k % DELAY] * td;
+            delay[k % DELAY] = out;
+            if k == 1000 {
+                assert!(tuner.gains_pid().is_none());
+            }
+        }
+        let period = tuner.period_estimate().unwrap();
+        assert!((period - 2.0).abs() < 0.01);
+        assert!((tuner.amplitude_estimate().unwrap() - 0.5).abs() < 0.01);
+        let ku = 4.0 / (core::f64::consts::PI * 0.5);
+        let (kp, ki, kd) = tuner.gains_pid().unwrap();
+        assert!((kp - 0.6 * ku).abs() / kp < 0.01);
+        assert!((ki - kp * 2.0 / period).abs() < 1e-9 && (kd - kp * period / 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn limits_try_set_nan() {
+        let mut limits = super::Limits::try_new(-1.0, 1.0).unwrap();
+        assert_eq!(limits.try_set_upper(f64::NAN).unwrap_err(), super::PidError::InvalidValue);
+        assert_eq!(limits.try_set_lower(f64::NAN).unwrap_err(), super::PidError::InvalidValue);
+        assert_eq!(limits.try_set_upper(-2.0).unwrap_err(), super::PidError::LimitOutBound);
+        assert_eq!(limits, super::Limits::try_new(-1.0, 1.0).unwrap());
+    }
+
+    #[test]
+    fn saturation_flags() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 0.0);
+        pid.set_all_limits(3.0, 2.0, 10.0, 10.0);
+        pid.setpoint = 1.0;
+        let out = pid.step(super::PidIn::new(0.0, 1.0));
+        assert!(!out.saturation.any());
+        assert_eq!(out, super::PidOut::new(1.0, 1.0, 0.0, 2.0));
+        pid.setpoint = 5.0;
+        let flags = pid.step(super::PidIn::new(0.0, 1.0)).saturation;
+        assert!(flags.kp_saturated && !flags.ki_saturated && !flags.kd_saturated && flags.output_saturated);
+        assert_eq!(super::Limits::try_new(0.0, 1.0).unwrap().clamp_with_flag(2.0), (1.0, true));
+    }
+
+    #[test]
+    fn observe() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        pid.init(3.0, 0.0);
+        assert_eq!(pid.observe(1.0, 0.5), 2.0);
+        assert_eq!((pid.ki.accumulate(), pid.kd.prev_measurement(), pid.step_count()), (1.0, 1.0, 0));
+        let out = pid.step(super::PidIn::new(1.0, 0.5));
+        assert_eq!((out.p, out.i, out.d), (2.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn partial_resets() {
+        let mut pid = super::PidCtrl::new_with_pid(0.0, 1.0, 1.0);
+        pid.setpoint = 1.0;
+        pid.step(super::PidIn::new(0.0, 1.0));
+        pid.reset_derivative(5.0);
+        assert_eq!(pid.ki.accumulate(), 1.0);
+        let out = pid.step(super::PidIn::new(5.0, 1.0));
+        assert_eq!((out.i, out.d), (-3.0, 0.0));
+        pid.reset_integral();
+        assert_eq!((pid.ki.accumulate(), pid.kd.prev_measurement()), (0.0, 5.0));
+    }
+
+    #[test]
+    fn integrated_time() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 0.0);
+        pid.step(super::PidIn::new(0.0, 0.5));
+        pid.step(super::PidIn::new(0.0, 1.5));
+        assert_eq!(pid.ki.integrated_time(), 2.0);
+        pid.set_manual(1.0).step(super::PidIn::new(0.0, 0.25));
+        assert_eq!(pid.ki.integrated_time(), 2.25);
+        pid.reset();
+        assert_eq!(pid.ki.integrated_time(), 0.0);
+    }
+
+    #[test]
+    fn set_asymmetric_limit() {
+        let mut limits = super::Limits::try_new(-1.0, 1.0).unwrap();
+        // would fail as try_set_lower(5.0) followed by try_set_upper(10.0)
+        limits.set_asymmetric_limit(5.0, 10.0).unwrap();
+        assert_eq!((limits.lower(), limits.upper()), (5.0, 10.0));
+        assert_eq!(limits.set_asymmetric_limit(2.0, 1.0).unwrap_err(), super::PidError::LimitOutBound);
+        assert_eq!(limits.clamp(-3.0), 5.0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn compare_gains() {
+        let mut baseline = super::PidCtrl::new_with_pid(1.0, 1.0, 0.0);
+        baseline.setpoint = 1.0;
+        baseline.ki.set_accumulate(0.5);
+        let mut candidate = baseline;
+        candidate.kp.set_scale(2.0);
+        candidate.ki.set_accumulate(0.0);
+        let (a, b) = super::compare_gains(baseline, candidate, &[0.0, 0.5], 1.0);
+        let out = |outs: &[super::PidOut<f64>]| outs.iter().map(|out| out.out).collect::<std::vec::Vec<_>>();
+        assert_eq!(out(&a), [2.5, 2.5]);
+        assert_eq!(out(&b), [3.5, 3.0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn csv() {
+        let out = super::PidOut::new(1.0, -0.5, 0.0, 0.5);
+        assert_eq!(out.to_csv_row(0.25), "0.25,1,-0.5,0,0.5\n");
+        let mut row = std::string::String::new();
+        out.write_csv_row(&mut row, 0.25).unwrap();
+        assert_eq!(row, out.to_csv_row(0.25));
+
+        let mut logger = super::CsvLogger::new();
+        logger.push(0.0, out);
+        logger.push(1.0, super::PidOut::new(2.0, 0.0, 0.0, 2.0));
+        assert_eq!(logger.to_csv(), "timestamp,p,i,d,out\n0,1,-0.5,0,0.5\n1,2,0,0,2\n");
+    }
+
+    #[test]
+    fn new_with_pid_and_init() {
+        let mut expected = super::PidCtrl::new_with_pid(1.0, 2.0, 3.0);
+        expected.init(4.0, 5.0);
+        assert_eq!(super::PidCtrl::new_with_pid_and_init(1.0, 2.0, 3.0, 4.0, 5.0), expected);
+    }
+
+    #[test]
+    fn tustin_derivative() {
+        let mut tustin = super::KDTerm::new_tustin(2.0, 0.5);
+        let mut backward = super::KDTerm::new();
+        backward.set_scale(2.0);
+        for m in [1.0, 3.0, 2.0, 2.0] {
+            assert_eq!(tustin.step(m), backward.step(m, 0.5));
+        }
+
+        // with tf = T: y[n] = (-2 * (x[n] - x[n-1]) + y[n-1]) / 3
+        let mut filtered = super::KDTerm::new_tustin(1.0, 1.0);
+        filtered.set_filter_time_constant(1.0).unwrap();
+        assert_eq!(filtered.step(3.0), -2.0);
+        assert_eq!(filtered.step(3.0), -2.0 / 3.0);
+        assert!(filtered.set_filter_time_constant(-1.0).is_err());
+        assert!(super::BilinearKDTerm::try_new(1.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn step_fixed() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        let mut expected = pid;
+        assert_eq!(pid.set_sample_period(0.0).unwrap_err(), super::PidError::InvalidValue);
+        pid.set_sample_period(0.1).unwrap();
+        assert_eq!(pid.step_fixed(2.0), expected.step(super::PidIn::new(2.0, 0.1)));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "call set_sample_period first")]
+    fn step_fixed_without_period() {
+        super::PidCtrl::new_with_pid(1.0, 0.0, 0.0).step_fixed(1.0);
+    }
+
+    #[test]
+    fn step_detailed() {
+        let mut pid = super::PidCtrl::new_with_pid(2.0, 1.0, 0.0);
+        pid.kp.limits.set_limit(1.5);
+        pid.limits.set_limit(2.0);
+        pid.setpoint = 1.0;
+        let mut plain = pid;
+        let detailed = pid.step_detailed(super::PidIn::new(0.0, 1.0));
+        assert_eq!(detailed.base, plain.step(super::PidIn::new(0.0, 1.0)));
+        assert_eq!((detailed.p_raw, detailed.i_raw, detailed.d_raw), (2.0, 1.0, 0.0));
+        assert_eq!((detailed.output_raw, detailed.error, detailed.base.out), (2.5, 1.0, 2.0));
+        assert!(detailed.base.saturation.kp_saturated && detailed.base.saturation.output_saturated);
+    }
+
+    #[test]
+    fn back_calc_partial_saturation() {
+        let mut pid = super::PidCtrl::new_with_pid(0.0, 1.0, 0.0);
+        pid.ki.limits = super::Limits::try_new(-2.0, 2.0).unwrap();
+        pid.limits.set_limit(1.0);
+        pid.ki.set_output_tracking_gain(0.5).unwrap().set_term_tracking_gain(0.25).unwrap();
+        pid.setpoint = 3.0;
+        // i = 2 (raw 3): output error 1 - 2 = -1, term error 2 - 3 = -1
+        let out = pid.step(super::PidIn::new(0.0, 1.0));
+        assert_eq!((out.i, out.out), (2.0, 1.0));
+        assert_eq!(pid.ki.accumulate(), 2.0 - 0.5 - 0.25);
+
+        let mut ki = super::KITerm::new();
+        ki.apply_back_calc_correction(1.0, 1.0);
+        assert_eq!(ki.accumulate(), 0.0);
+    }
+
+    #[test]
+    fn preview_step() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        pid.init(1.0, 0.0);
+        let before = pid;
+        let preview = pid.preview_step(super::PidIn::new(0.5, 1.0));
+        assert_eq!(pid, before);
+        assert_eq!(preview, pid.step(super::PidIn::new(0.5, 1.0)));
+    }
+
+    #[test]
+    fn try_set_limit() {
+        let mut limits = super::Limits::new();
+        assert_eq!((limits.try_set_limit(2.0).unwrap().lower(), limits.upper()), (-2.0, 2.0));
+        for val in [-1.0, 0.0, f64::NAN] {
+            assert_eq!(limits.try_set_limit(val).unwrap_err(), super::PidError::InvalidValue);
+        }
+        assert_eq!(limits.upper(), 2.0);
+    }
+
+    #[test]
+    fn ideal_form() {
+        let pid = super::PidCtrl::from_ideal_form(2.0, 4.0, 0.25);
+        assert_eq!((pid.kp.scale, pid.ki.scale, pid.kd.scale), (2.0, 0.5, 0.5));
+        assert_eq!(pid.to_ideal_form(), (2.0, 4.0, 0.25));
+    }
+
+    #[test]
+    fn iae_ise() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        pid.setpoint = 1.0;
+        pid.step(super::PidIn::new(3.0, 0.5));
+        pid.step(super::PidIn::new(0.0, 2.0));
+        assert_eq!((pid.iae(), pid.ise()), (3.0, 4.0));
+        pid.reset_iae();
+        assert_eq!((pid.iae(), pid.ise()), (0.0, 4.0));
+        pid.reset();
+        assert_eq!(pid.ise(), 0.0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pid_in_from_time() {
+        let input = super::PidIn::from_duration(1.0, std::time::Duration::from_millis(250));
+        assert_eq!(input, super::PidIn::new(1.0, 0.25));
+
+        let start = std::time::Instant::now();
+        let now = start + std::time::Duration::from_millis(500);
+        let mut last = start;
+        assert_eq!(super::PidIn::from_instant(2.0, now, &mut last), super::PidIn::new(2.0, 0.5));
+        assert_eq!(last, now);
+        assert_eq!(super::PidIn::from_instant(2.0, start, &mut last), super::PidIn::new(2.0, 0.0));
+    }
+
+    #[test]
+    fn pid_out_sub() {
+        let a = super::PidOut::new(1.0, -2.0, 0.5, -0.5);
+        let b = super::PidOut::new(0.5, 1.0, 0.5, 2.0);
+        assert_eq!(a - a, super::PidOut::default());
+        assert_eq!(a - b, super::PidOut::new(0.5, -3.0, 0.0, -2.5));
+        assert_eq!(a - b + b, a);
+        let mut c = a;
+        c -= b;
+        assert_eq!(c, a - b);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn config_summary() {
+        let mut pid = super::PidCtrl::new_with_pid(2.0, 0.5, 0.0);
+        pid.setpoint = 1.0;
+        pid.limits = super::Limits::try_new(-5.0, 10.0).unwrap();
+        pid.step(super::PidIn::new(0.0, 1.0));
+        let expected = "PID kp=2 ki=0.5 kd=0 sp=1 out_lim=[-5, 10]";
+        assert_eq!(pid.config_summary(), expected);
+        let mut summary = std::string::String::new();
+        pid.write_config_summary(&mut summary).unwrap();
+        assert_eq!(summary, expected);
+        assert_eq!(super::PidCtrl::new_with_pid(1.0, 0.0, 0.0).config_summary(), "PID kp=1 ki=0 kd=0 sp=0");
+    }
+
+    #[test]
+    fn step_conditional_clamp() {
+        let mut ki = super::KITerm::new();
+        ki.set_scale(1.0);
+        ki.limits = super::Limits::try_new(-1.0, 1.0).unwrap();
+        assert_eq!(ki.step_conditional_clamp(0.75, 1.0), 0.75);
+        // 0.75 + 0.5 would cross the bound, so the accumulator holds
+        assert_eq!(ki.step_conditional_clamp(0.5, 1.0), 0.75);
+        assert_eq!(ki.step_conditional_clamp(-0.5, 1.0), 0.25);
+        assert_eq!(ki.integrated_time(), 3.0);
+        let mut clamped = ki;
+        assert_eq!(clamped.step(2.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn setpoint_limits() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        pid.set_setpoint_limits(super::Limits::try_new(0.0, 10.0).unwrap(), super::SetpointLimitMode::Clamp);
+        pid.setpoint = 20.0;
+        assert_eq!(pid.step(super::PidIn::new(4.0, 1.0)).out, 6.0);
+        assert_eq!(pid.setpoint, 20.0);
+        assert_eq!(pid.step_with_setpoint(super::PidIn::new(4.0, 1.0), -5.0).out, -4.0);
+        pid.clear_setpoint_limits();
+        assert_eq!(pid.step(super::PidIn::new(4.0, 1.0)).out, 16.0);
+    }
+
+    #[test]
+    fn gain_sensitivity() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        pid.init(2.0, 0.0);
+        let before = pid;
+        let input = super::PidIn::new(1.0, 0.5);
+        let (kp, ki, kd) = pid.gain_sensitivity(input, 0.5);
+        assert_eq!(pid, before);
+        let base = pid.preview_step(input).out;
+        // d out / d gain: the error, its integral and the measurement derivative
+        assert_eq!((kp.out - base, ki.out - base, kd.out - base), (0.5, 0.25, -1.0));
+    }
+
+    #[test]
+    fn limits_is_default() {
+        let mut limits = super::Limits::<f64>::default();
+        assert!(limits.is_default());
+        limits.try_set_upper(1.0).unwrap();
+        assert!(!limits.is_default());
+        assert!(!super::Limits::try_new(f64::INFINITY, f64::INFINITY).unwrap().is_default());
+    }
+
+    #[test]
+    fn kd_decimation() {
+        let mut kd = super::KDTerm::new();
+        kd.set_scale(1.0);
+        kd.set_decimation_factor(3);
+        let outs: [f64; 5] = [1.0, 2.0, 3.0, 4.0, 5.0].map(|m| kd.step(m, 0.5));
+        // computed on the first and fourth steps, the latter over 1.5 time units
+        assert_eq!(outs, [-2.0, -2.0, -2.0, -2.0, -2.0]);
+        assert_eq!(kd.prev_measurement(), 4.0);
+        kd.set_decimation_factor(0);
+        assert_eq!(kd.step(6.0, 0.5), -4.0);
+    }
+
+    #[test]
+    fn step_all() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 1.0, 1.0);
+        pid.init(1.0, 0.0);
+        let mut sequential = pid;
+        let inputs = [0.0, 0.5, 2.0].map(|m| super::PidIn::new(m, 0.5));
+        let mut outs = [super::PidOut::default(); 3];
+        for (slot, out) in outs.iter_mut().zip(pid.step_all(inputs.into_iter())) {
+            *slot = out;
+        }
+        assert_eq!(outs, inputs.map(|input| sequential.step(input)));
+        assert_eq!(pid, sequential);
+    }
+
+    #[test]
+    fn nvm_bytes_roundtrip() {
+        let mut pid = super::PidCtrl::new_with_pid(3.0f32, 2.0, 1.0);
+        pid.kp.limits.set_limit(4.0);
+        pid.ki.limits = super::Limits::try_new(-1.0, 2.0).unwrap();
+        pid.limits = super::Limits::try_new(0.0, 100.0).unwrap();
+        pid.step(super::PidIn::new(1.0, 1.0));
+        let bytes = pid.to_bytes();
+        assert_eq!(bytes.len(), super::PidCtrl::<f32>::NVM_LEN);
+        assert_eq!(bytes[..4], 3.0f32.to_le_bytes());
+        let restored = super::PidCtrl::<f32>::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.to_bytes(), bytes);
+        assert_eq!((restored.ki.limits, restored.ki.accumulate()), (pid.ki.limits, 0.0));
+
+        let pid = super::PidCtrl::new_with_pid(0.5f64, 0.25, 0.0);
+        assert_eq!(super::PidCtrl::<f64>::from_bytes(&pid.to_bytes()).unwrap(), pid);
+        // erased flash reads as all ones, i.e. NaN
+        assert!(super::PidCtrl::<f64>::from_bytes(&[0xff; 88]).is_none());
+        let mut bytes = pid.to_bytes();
+        // output limits [1, -1]
+        bytes[72..80].copy_from_slice(&1.0f64.to_le_bytes());
+        bytes[80..].copy_from_slice(&(-1.0f64).to_le_bytes());
+        assert!(super::PidCtrl::<f64>::from_bytes(&bytes).is_none());
+    }
+
+    #[test]
+    fn set_setpoint() {
+        let mut pid = super::PidCtrl::new_with_pid(1.0, 0.0, 0.0);
+        pid.init(5.0, 0.0).set_setpoint(7.0);
+        assert_eq!(pid.setpoint, 7.0);
+        assert_eq!(pid.step(super::PidIn::new(0.0, 1.0)).out, 7.0);
     }
 }
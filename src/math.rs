@@ -0,0 +1,149 @@
+//! `FloatCore`-only implementations of the few transcendental functions the crate needs,
+//! so none of them depend on `std` or `libm`.
+use num_traits::float::FloatCore;
+
+pub(crate) fn cast<T: FloatCore>(val: f64) -> T {
+    T::from(val).unwrap()
+}
+
+/// `e^x`, range-reduced to `e^n * e^r` with `|r| <= 0.5` and a Taylor series for `e^r`.
+pub(crate) fn exp<T: FloatCore>(x: T) -> T {
+    if x.is_nan() {
+        return x;
+    }
+    let n = x.round();
+    let n_int = match n.to_i32() {
+        Some(n_int) => n_int,
+        None if x > T::zero() => return T::infinity(),
+        None => return T::zero(),
+    };
+    let r = x - n;
+    let mut term = T::one();
+    let mut sum = T::one();
+    for k in 1..20 {
+        term = term * r / cast(k as f64);
+        sum = sum + term;
+    }
+    sum * cast::<T>(core::f64::consts::E).powi(n_int)
+}
+
+/// Square root by Newton's method, starting from a power of two close to the result.
+pub(crate) fn sqrt<T: FloatCore>(x: T) -> T {
+    if x.is_nan() || x < T::zero() {
+        return T::nan();
+    }
+    if x == T::zero() || x.is_infinite() {
+        return x;
+    }
+    let (mantissa, exponent, _) = x.integer_decode();
+    let magnitude = exponent as i32 + 64 - mantissa.leading_zeros() as i32;
+    let two: T = cast(2.0);
+    let mut guess = two.powi(magnitude / 2);
+    for _ in 0..8 {
+        guess = (guess + x / guess) / two;
+    }
+    guess
+}
+
+/// Sine and cosine of `x`, reduced to `|r| <= pi/4` around the nearest quarter turn.
+pub(crate) fn sin_cos<T: FloatCore>(x: T) -> (T, T) {
+    if !x.is_finite() {
+        return (T::nan(), T::nan());
+    }
+    let half_pi: T = cast(core::f64::consts::FRAC_PI_2);
+    let quarter = (x / half_pi).round();
+    let r = x - quarter * half_pi;
+    let r2 = r * r;
+    let mut sin_term = r;
+    let mut cos_term = T::one();
+    let mut sin = sin_term;
+    let mut cos = cos_term;
+    for k in 1..12 {
+        sin_term = -sin_term * r2 / cast(((2 * k) * (2 * k + 1)) as f64);
+        cos_term = -cos_term * r2 / cast(((2 * k - 1) * (2 * k)) as f64);
+        sin = sin + sin_term;
+        cos = cos + cos_term;
+    }
+    let quadrant = (quarter % cast(4.0) + cast(4.0)) % cast(4.0);
+    match quadrant.to_u8() {
+        Some(0) => (sin, cos),
+        Some(1) => (cos, -sin),
+        Some(2) => (-sin, -cos),
+        _ => (-cos, sin),
+    }
+}
+
+/// Arctangent, halved twice with `atan(z) = 2 atan(z / (1 + sqrt(1 + z^2)))` before the series.
+fn atan<T: FloatCore>(z: T) -> T {
+    if z.abs() > T::one() {
+        let half_pi: T = cast(core::f64::consts::FRAC_PI_2);
+        return half_pi * z.signum() - atan(T::one() / z);
+    }
+    let mut reduced = z;
+    for _ in 0..2 {
+        reduced = reduced / (T::one() + sqrt(T::one() + reduced * reduced));
+    }
+    let r2 = reduced * reduced;
+    let mut power = reduced;
+    let mut sum = reduced;
+    for k in 1..14 {
+        power = -power * r2;
+        sum = sum + power / cast((2 * k + 1) as f64);
+    }
+    sum * cast(4.0)
+}
+
+/// Four-quadrant arctangent of `y / x`, in radians.
+pub(crate) fn atan2<T: FloatCore>(y: T, x: T) -> T {
+    let pi: T = cast(core::f64::consts::PI);
+    if x > T::zero() {
+        atan(y / x)
+    } else if x < T::zero() {
+        atan(y / x) + pi * y.signum()
+    } else if y != T::zero() {
+        pi / cast(2.0) * y.signum()
+    } else {
+        T::zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn exp() {
+        for x in [-700.0f64, -20.5, -1.0, -0.3, 0.0, 0.25, 1.0, 3.7, 50.0, 700.0] {
+            let expected = x.exp();
+            assert!((super::exp(x) - expected).abs() <= expected * 1e-13, "{x}");
+        }
+        assert_eq!(super::exp(0.0f32), 1.0);
+        assert_eq!(super::exp(f64::NEG_INFINITY), 0.0);
+        assert_eq!(super::exp(f64::INFINITY), f64::INFINITY);
+        assert_eq!(super::exp(1e10f64), f64::INFINITY);
+    }
+
+    #[test]
+    fn sqrt() {
+        for x in [1e-310f64, 1e-20, 0.5, 1.0, 2.0, 3.0, 1e5, 1.7e300] {
+            assert!((super::sqrt(x) - x.sqrt()).abs() <= x.sqrt() * 1e-15, "{x}");
+        }
+        assert_eq!(super::sqrt(4.0f32), 2.0);
+        assert_eq!(super::sqrt(0.0f64), 0.0);
+        assert!(super::sqrt(-1.0f64).is_nan());
+    }
+
+    #[test]
+    fn sin_cos() {
+        for x in [-10.0f64, -3.0, -1.5, -0.1, 0.0, 0.7, 1.6, 3.1, 4.0, 5.5, 100.0] {
+            let (sin, cos) = super::sin_cos(x);
+            assert!((sin - x.sin()).abs() < 1e-14, "{x}");
+            assert!((cos - x.cos()).abs() < 1e-14, "{x}");
+        }
+    }
+
+    #[test]
+    fn atan2() {
+        for (y, x) in [(1.0f64, 1.0), (0.3, -2.0), (-5.0, 0.1), (-1.0, -1.0), (2.0, 0.0), (0.0, -1.0)] {
+            assert!((super::atan2(y, x) - y.atan2(x)).abs() < 1e-14, "{y} {x}");
+        }
+    }
+}
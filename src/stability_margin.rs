@@ -0,0 +1,105 @@
+//! Gain and phase margins of a PID loop around a plant given by its poles.
+//!
+//! The plant is `G(s) = prod(-p) / prod(s - p)` over `plant_poles`, i.e. normalized to unit
+//! DC gain; fold the plant gain into `kp`, `ki` and `kd`. Each `(re, im)` pair is one pole,
+//! so list both members of a complex conjugate pair. The controller is the discrete PID of
+//! `analysis::pid_frequency_response`. Margins are found by sweeping up to the Nyquist
+//! frequency of `sample_period`; the plant poles must be stable.
+use crate::analysis::pid_frequency_response;
+use crate::math::{self, cast};
+use num_traits::float::FloatCore;
+
+const SWEEP_POINTS: usize = 2000;
+const BISECTIONS: usize = 60;
+
+/// `1 / |L(jw)|` at the first frequency where the loop phase crosses -180 degrees, or
+/// infinity if it never does.
+pub fn gain_margin<T: FloatCore + Default>(kp: T, ki: T, kd: T, plant_poles: &[(T, T)], sample_period: T) -> T {
+    let phase_gap = |omega: T| loop_response(kp, ki, kd, plant_poles, omega, sample_period).1 + cast(180.0);
+    match crossing(phase_gap, sample_period) {
+        Some(omega) => T::one() / loop_response(kp, ki, kd, plant_poles, omega, sample_period).0,
+        None => T::infinity(),
+    }
+}
+
+/// `180 + phase(L(jw))` in degrees at the first frequency where `|L(jw)|` crosses one, or
+/// infinity if it never does.
+pub fn phase_margin<T: FloatCore + Default>(kp: T, ki: T, kd: T, plant_poles: &[(T, T)], sample_period: T) -> T {
+    let gain_gap = |omega: T| loop_response(kp, ki, kd, plant_poles, omega, sample_period).0 - T::one();
+    match crossing(gain_gap, sample_period) {
+        Some(omega) => cast::<T>(180.0) + loop_response(kp, ki, kd, plant_poles, omega, sample_period).1,
+        None => T::infinity(),
+    }
+}
+
+// Magnitude and unwrapped phase in degrees of the open loop.
+fn loop_response<T: FloatCore + Default>(
+    kp: T, ki: T, kd: T, plant_poles: &[(T, T)], omega: T, sample_period: T,
+) -> (T, T) {
+    let (mut magnitude, mut phase) = pid_frequency_response(kp, ki, kd, omega, sample_period);
+    for &(re, im) in plant_poles {
+        // (-p) / (jw - p), one factor at a time so the phase stays unwrapped
+        magnitude = magnitude * math::sqrt(re * re + im * im) / math::sqrt(re * re + (omega - im) * (omega - im));
+        phase = phase + (math::atan2(-im, -re) - math::atan2(omega - im, -re)).to_degrees();
+    }
+    (magnitude, phase)
+}
+
+// First sign change of `f` on a log-spaced sweep up to the Nyquist frequency, refined by
+// bisection.
+fn crossing<T: FloatCore, F: Fn(T) -> T>(f: F, sample_period: T) -> Option<T> {
+    let nyquist = cast::<T>(core::f64::consts::PI) / sample_period;
+    // six decades below Nyquist
+    let omega_min = nyquist * cast(1e-6);
+    let step = cast::<T>(6.0 * core::f64::consts::LN_10 / (SWEEP_POINTS - 1) as f64);
+    let mut lo = omega_min;
+    let mut f_lo = f(lo);
+    for k in 1..SWEEP_POINTS {
+        let hi = omega_min * math::exp(step * cast(k as f64));
+        let f_hi = f(hi);
+        if (f_lo > T::zero()) != (f_hi > T::zero()) {
+            let (mut a, mut b) = (lo, hi);
+            for _ in 0..BISECTIONS {
+                let mid = (a + b) / (T::one() + T::one());
+                if (f(mid) > T::zero()) == (f_lo > T::zero()) {
+                    a = mid;
+                } else {
+                    b = mid;
+                }
+            }
+            return Some((a + b) / (T::one() + T::one()));
+        }
+        lo = hi;
+        f_lo = f_hi;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn first_order() {
+        // 2 / (s + 1): |L| = 1 at w = sqrt(3), where the phase is -60 degrees
+        let poles = [(-1.0f64, 0.0)];
+        let pm = super::phase_margin(2.0, 0.0, 0.0, &poles, 1e-3);
+        assert!((pm - 120.0).abs() < 1e-6);
+        assert!(super::gain_margin(2.0, 0.0, 0.0, &poles, 1e-3).is_infinite());
+    }
+
+    #[test]
+    fn third_order() {
+        // 2 / (s + 1)^3: the phase reaches -180 degrees at w = sqrt(3), where |G| = 1 / 8
+        let poles = [(-1.0f64, 0.0); 3];
+        let gm = super::gain_margin(2.0, 0.0, 0.0, &poles, 1e-3);
+        assert!((gm - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn second_order_complex() {
+        // w0^2 / (s^2 + 2 zeta w0 s + w0^2) with w0 = 1, zeta = 0.5 under unity gain:
+        // |G| = 1 at w = 1, where the phase is -90 degrees
+        let poles = [(-0.5f64, 0.75f64.sqrt()), (-0.5, -0.75f64.sqrt())];
+        let pm = super::phase_margin(1.0, 0.0, 0.0, &poles, 1e-3);
+        assert!((pm - 90.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,98 @@
+//! Simulated plants for closed-loop tests, behind the `test-utils` feature. Both integrate
+//! with forward Euler, so keep `dt` well below the plant time constants.
+use num_traits::float::FloatCore;
+
+/// First-order lag `tau * dy/dt = gain * u - y`.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct FirstOrderPlant<T: FloatCore + Default> {
+    pub gain: T,
+    pub time_constant: T,
+    /// The plant output `y`.
+    pub state: T,
+}
+
+impl<T: FloatCore + Default> FirstOrderPlant<T> {
+    /// Starts at rest, `state = 0`.
+    pub fn new(gain: T, time_constant: T) -> Self {
+        FirstOrderPlant{gain, time_constant, state: T::zero()}
+    }
+
+    /// Applies `input` for `dt` and returns the new output.
+    pub fn step(&mut self, input: T, dt: T) -> T {
+        self.state = self.state + (self.gain * input - self.state) * dt / self.time_constant;
+        self.state
+    }
+}
+
+/// Second-order system `y'' + 2 * damping_ratio * natural_freq * y' + natural_freq^2 * y =
+/// natural_freq^2 * gain * u`.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct SecondOrderPlant<T: FloatCore + Default> {
+    pub gain: T,
+    pub natural_freq: T,
+    pub damping_ratio: T,
+    /// The plant output `y`.
+    pub state: T,
+    /// `dy/dt`.
+    pub velocity: T,
+}
+
+impl<T: FloatCore + Default> SecondOrderPlant<T> {
+    /// Starts at rest, `state = velocity = 0`.
+    pub fn new(gain: T, natural_freq: T, damping_ratio: T) -> Self {
+        SecondOrderPlant{gain, natural_freq, damping_ratio, state: T::zero(), velocity: T::zero()}
+    }
+
+    /// Applies `input` for `dt` and returns the new output. The velocity is updated first
+    /// (semi-implicit Euler), which keeps undamped oscillations from growing.
+    pub fn step(&mut self, input: T, dt: T) -> T {
+        let w2 = self.natural_freq * self.natural_freq;
+        let two_zeta_w = (self.damping_ratio + self.damping_ratio) * self.natural_freq;
+        let accel = w2 * (self.gain * input - self.state) - two_zeta_w * self.velocity;
+        self.velocity = self.velocity + accel * dt;
+        self.state = self.state + self.velocity * dt;
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PidCtrl, PidIn};
+
+    #[test]
+    fn first_order_step_response() {
+        let mut plant = super::FirstOrderPlant::new(2.0, 1.0);
+        let mut y = 0.0f64;
+        for _ in 0..10_000 {
+            y = plant.step(1.0, 1e-3);
+        }
+        // 1 - exp(-10) of the final value
+        assert!((y - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn closed_loop_first_order() {
+        let mut plant = super::FirstOrderPlant::new(2.0, 1.0);
+        let mut pid = PidCtrl::new_with_pid(1.0, 1.0, 0.0);
+        pid.init(1.0, 0.0);
+        let mut y = 0.0f64;
+        for _ in 0..20_000 {
+            let u = pid.step(PidIn::new(y, 1e-3)).out;
+            y = plant.step(u, 1e-3);
+        }
+        assert!((y - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn closed_loop_second_order() {
+        let mut plant = super::SecondOrderPlant::new(1.0, 2.0, 0.3);
+        let mut pid = PidCtrl::new_with_pid(2.0, 2.0, 0.5);
+        pid.init(1.0, 0.0);
+        let mut y = 0.0f64;
+        for _ in 0..30_000 {
+            let u = pid.step(PidIn::new(y, 1e-3)).out;
+            y = plant.step(u, 1e-3);
+        }
+        assert!((y - 1.0).abs() < 1e-4);
+    }
+}
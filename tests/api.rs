@@ -11,6 +11,8 @@ fn api() {
 
 #[test]
 fn example() {
+    // outputs are compared within a tolerance to stay portable across FPUs
+    const EPS: f64 = 1e-9;
     let mut pid = pid_ctrl::PidCtrl::new_with_pid(3.0, 2.0, 1.0);
 
     let setpoint = 5.0;
@@ -21,51 +23,73 @@ fn example() {
 
     let measurement = 0.0;
     let time_delta = 1.0;
-    assert_eq!(
-        pid.step(pid_ctrl::PidIn::new(measurement, time_delta)), 
-        pid_ctrl::PidOut::new(15.0, 10.0, 0.0, 25.0)
-    );
+    assert!(pid
+        .step(pid_ctrl::PidIn::new(measurement, time_delta))
+        .approx_eq(&pid_ctrl::PidOut::new(15.0, 10.0, 0.0, 25.0), EPS));
 
     // changing pid constants
     pid.kp.set_scale(4.0);
-    assert_eq!(
-        pid.step(pid_ctrl::PidIn::new(measurement, time_delta)), 
-        pid_ctrl::PidOut::new(20.0, 20.0, 0.0, 40.0)
-    );
+    assert!(pid
+        .step(pid_ctrl::PidIn::new(measurement, time_delta))
+        .approx_eq(&pid_ctrl::PidOut::new(20.0, 20.0, 0.0, 40.0), EPS));
 
     // setting symmetrical limits around zero
     pid.kp.limits.set_limit(10.0);
-    assert_eq!(
-        pid.step(pid_ctrl::PidIn::new(measurement, time_delta)), 
-        pid_ctrl::PidOut::new(10.0, 30.0, 0.0, 40.0)
-    );
+    assert!(pid
+        .step(pid_ctrl::PidIn::new(measurement, time_delta))
+        .approx_eq(&pid_ctrl::PidOut::new(10.0, 30.0, 0.0, 40.0), EPS));
 
     let time_delta = 0.5;
-    assert_eq!(
-        pid.step(pid_ctrl::PidIn::new(measurement, time_delta)), 
-        pid_ctrl::PidOut::new(10.0, 35.0, 0.0, 45.0)
-    );
+    assert!(pid
+        .step(pid_ctrl::PidIn::new(measurement, time_delta))
+        .approx_eq(&pid_ctrl::PidOut::new(10.0, 35.0, 0.0, 45.0), EPS));
 
     // setting upper limits returns error if new value conflicts with lower limit
     pid.ki.limits.try_set_upper(28.0).unwrap();  
-    assert_eq!(
-        pid.step(pid_ctrl::PidIn::new(measurement, time_delta)), 
-        pid_ctrl::PidOut::new(10.0, 28.0, 0.0, 38.0)
-    );
+    assert!(pid
+        .step(pid_ctrl::PidIn::new(measurement, time_delta))
+        .approx_eq(&pid_ctrl::PidOut::new(10.0, 28.0, 0.0, 38.0), EPS));
 
     // time_delta gets clamped to Float::epsilon() - Float::infinity()
     let measurement = 1.0;
     let time_delta = -7.0;
     pid.kd.set_scale(num_traits::float::FloatCore::epsilon());
-    assert_eq!(pid.step(
-        pid_ctrl::PidIn::new(measurement, time_delta)), 
-        pid_ctrl::PidOut::new(10.0, 28.0, -1.0, 37.0)
-    );
+    assert!(pid
+        .step(pid_ctrl::PidIn::new(measurement, time_delta))
+        .approx_eq(&pid_ctrl::PidOut::new(10.0, 28.0, -1.0, 37.0), EPS));
 
     // configure setpoint directly
     pid.setpoint = 1.0;
-    assert_eq!(pid.step(
-        pid_ctrl::PidIn::new(measurement, time_delta)), 
-        pid_ctrl::PidOut::new(0.0, 28.0, 0.0, 28.0)
-    );
-}
\ No newline at end of file
+    assert!(pid
+        .step(pid_ctrl::PidIn::new(measurement, time_delta))
+        .approx_eq(&pid_ctrl::PidOut::new(0.0, 28.0, 0.0, 28.0), EPS));
+}
+
+#[test]
+fn limits_hash_signed_zero() {
+    use std::hash::{Hash, Hasher};
+    let hash = |limits: &pid_ctrl::Limits<f64>| {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        limits.hash(&mut hasher);
+        hasher.finish()
+    };
+    let a = pid_ctrl::Limits::try_new(-0.0, 1.0).unwrap();
+    let b = pid_ctrl::Limits::try_new(0.0, 1.0).unwrap();
+    assert_eq!(a, b);
+    assert_eq!(hash(&a), hash(&b));
+    assert_ne!(hash(&a), hash(&pid_ctrl::Limits::try_new(0.0, 2.0).unwrap()));
+}
+
+#[test]
+fn limits_debug() {
+    assert_eq!(format!("{:?}", pid_ctrl::Limits::<f64>::default()), "Limits[-inf, inf]");
+    assert_eq!(format!("{:?}", pid_ctrl::Limits::try_new(-5.0, 10.0).unwrap()), "Limits[-5.0, 10.0]");
+}
+
+#[test]
+fn limits_display() {
+    let limits = pid_ctrl::Limits::try_new(-5.0f32, 10.0f32).unwrap();
+    assert_eq!(format!("{}", limits), "[-5, 10]");
+    assert_eq!(format!("{:.2}", limits), "[-5.00, 10.00]");
+    assert_eq!(format!("{}", pid_ctrl::Limits::<f64>::default()), "[-∞, +∞]");
+}